@@ -1,15 +1,26 @@
 use crate::{
     config::{parse_config, Config},
-    CLUSTERINGARGS, OUTPUT,
+    CLUSTERINGARGS, INPUT, OUTPUT,
+};
+use clap::{App, Arg, ArgMatches, ErrorKind};
+use serde::{Deserialize, Serialize};
+use std::{
+    env, fs,
+    path::{Path, PathBuf},
 };
-use clap::{App, Arg};
-use std::{env, fs, path::Path};
 
-pub fn parse_arguments() -> Config {
-    let matches = App::new("Detour Graph")
+/// Returns the command line interface of the program.
+fn app() -> App<'static> {
+    App::new("Detour Graph")
         .version("0.1.0")
         .author("Erik Andersen <3rik4ndersen@gmail.com>")
-        .about("Builds a Detour graph. Input is read from stdin. Currently supports GPX, PLT (Geolife Trajectories 1.3), AIS (AIS Brest 2009 at chorochronos), and some csv files.")
+        .about("Builds a Detour graph. Input is read from the given files or from stdin. Currently supports GPX, PLT (Geolife Trajectories 1.3), AIS (AIS Brest 2009 at chorochronos), and some csv files.")
+        .arg(
+            Arg::new("input")
+                .takes_value(true)
+                .multiple_values(true)
+                .help("Input files. If none are given, input is read from stdin"),
+        )
         .arg(
             Arg::new("out_path")
                 .short('o')
@@ -32,7 +43,6 @@ pub fn parse_arguments() -> Config {
                 .help("Amount of temporal slack. Should be at most 24 hours and always be postfixed with either 'h' for hours or 'm' for minutes. E.g. 4h or 15m. Defaults to 24h i.e. only the spatial dimensions are used in the clustering process."),
         ).arg(
             Arg::new("clustering_threshold")
-                .short('c')
                 .long("clustering-threshold")
                 .takes_value(true)
                 .help("Threshold used in clustering. When temporal slack is 24h the threshold corresponds to the maximal distance in meters between two points. Defaults to 50."),
@@ -42,14 +52,14 @@ pub fn parse_arguments() -> Config {
                 .short('e')
                 .long("edges")
                 .takes_value(false)
-                .help("Write edges to csv"),
+                .help("Write edges, their travel times and the edge each input trajectory is assigned to to csv and their corridors to GeoJSON"),
         )
         .arg(
             Arg::new("nodes")
                 .short('n')
                 .long("nodes")
                 .takes_value(false)
-                .help("Write nodes to csv"),
+                .help("Write nodes and their visits to csv"),
         )
         .arg(
             Arg::new("graph_dot")
@@ -76,8 +86,129 @@ pub fn parse_arguments() -> Config {
                 .long("use-medoids")
                 .takes_value(false)
 		.help("Use medoids as edge clusters representatives"),
+        ).arg(
+            Arg::new("use_dba")
+                .long("use-dba")
+                .takes_value(false)
+                .help("Use DTW barycenter averages as edge clusters representatives. The number of iterations is set by dba_iterations"),
+        ).arg(
+            Arg::new("segmentation")
+                .short('s')
+                .long("segmentation")
+                .takes_value(true)
+                .possible_values(["timeout", "stops"])
+                .help("How streams are cut into trajectories. 'timeout' only cuts when connection_timeout is exceeded, 'stops' also cuts at detected stops. Defaults to timeout."),
+        ).arg(
+            Arg::new("builder")
+                .short('b')
+                .long("builder")
+                .takes_value(true)
+                .possible_values(["v1", "v2"])
+                .help("Graph builder to use. 'v1' builds paths from detected stops and merges their nodes, 'v2' clusters trajectory endpoints. Defaults to v2."),
+        )
+        .arg(
+            Arg::new("vessels")
+                .long("vessels")
+                .takes_value(false)
+                .help("The input describes vessels, i.e. moving trajectories are classified as vessel traffic"),
+        )
+        .arg(
+            Arg::new("separate_modes")
+                .long("separate-modes")
+                .takes_value(false)
+                .help("Don't merge trajectories of different transport modes into the same edge"),
+        )
+        .arg(
+            Arg::new("weekly")
+                .long("weekly")
+                .takes_value(false)
+                .help("Fold the streams onto a week instead of a single day, s.t. each weekday is kept apart"),
+        )
+        .arg(
+            Arg::new("day_types")
+                .long("day-types")
+                .takes_value(true)
+                .possible_values(["weekend", "places"])
+                .conflicts_with("extend")
+                .help("Build a graph for each day type in a subfolder of the output. 'weekend' separates weekdays from weekends, 'places' clusters days by the places visited"),
+        )
+        .arg(
+            Arg::new("per_object")
+                .long("per-object")
+                .takes_value(false)
+                .conflicts_with_all(&["extend", "day_types", "holidays"])
+                .help("Build a separate graph for each object in a subfolder of the output instead of a graph shared by all objects. Objects are identified by the input, or by the file they are read from"),
         )
-        .get_matches();
+        .arg(
+            Arg::new("holidays")
+                .long("holidays")
+                .takes_value(true)
+                .conflicts_with("extend")
+                .help("File listing holidays as YYYY-MM-DD, one on each line. Holidays get a graph of their own. Implies --day-types weekend unless given"),
+        )
+        .arg(
+            Arg::new("threads")
+                .long("threads")
+                .takes_value(true)
+                .help("Number of threads used to build the graph. Defaults to the number of CPUs"),
+        )
+        .arg(
+            Arg::new("osm")
+                .long("osm")
+                .takes_value(true)
+                .help("OpenStreetMap extract (.osm or .osm.pbf) to match trajectories to. The matched way ids are stored with each edge"),
+        )
+        .arg(
+            Arg::new("extend")
+                .long("extend")
+                .takes_value(true)
+                .help("Previously written graph.json to extend with the input instead of building a new graph. Requires the v2 builder"),
+        )
+        .arg(
+            Arg::new("validate")
+                .long("validate")
+                .takes_value(true)
+                .help("Check that a previously written graph.json is valid instead of building a graph. Violations are printed to stdout as json"),
+        )
+        .arg(
+            Arg::new("stays")
+                .long("stays")
+                .takes_value(false)
+                .help("Write the stays detected in each stream to csv and GeoJSON"),
+        )
+        .arg(
+            Arg::new("segmentation_debug")
+                .long("segmentation-debug")
+                .takes_value(false)
+                .help("Write the motion and stop detector output for each point to csv. Useful for tuning minimum_velocity, epsilon_velocity, bbox_max_meters and bbox_min_minutes"),
+        )
+        .arg(
+            Arg::new("stats")
+                .long("stats")
+                .takes_value(false)
+                .help("Write statistics to json"),
+        )
+}
+
+/// Parses the command line arguments, including the combinations of them that clap can't check by itself.
+fn get_matches<I, T>(args: I) -> clap::Result<ArgMatches>
+where
+    I: IntoIterator<Item = T>,
+    T: Into<std::ffi::OsString> + Clone,
+{
+    let mut app = app();
+    let matches = app.try_get_matches_from_mut(args)?;
+    if matches.value_of("builder") == Some("v1") && matches.is_present("extend") {
+        return Err(app.error(
+            ErrorKind::ArgumentConflict,
+            "Only the v2 builder can extend a graph, use --builder v2 with --extend",
+        ));
+    }
+    Ok(matches)
+}
+
+pub fn parse_arguments() -> Config {
+    let matches = get_matches(env::args_os()).unwrap_or_else(|err| err.exit());
 
     let config = match matches.value_of("config_file") {
         Some(config) => {
@@ -110,6 +241,7 @@ pub fn parse_arguments() -> Config {
         clustering_args.temporal_slack = time_value;
     }
 
+    CLUSTERINGARGS.lock().unwrap().cluster_strategy = config.edge_representative;
     if matches.is_present("use_medoids") {
         let mut clustering_args = CLUSTERINGARGS.lock().unwrap();
         clustering_args.cluster_strategy = EdgeRepresentative::Medoid;
@@ -118,6 +250,69 @@ pub fn parse_arguments() -> Config {
         let mut clustering_args = CLUSTERINGARGS.lock().unwrap();
         clustering_args.cluster_strategy = EdgeRepresentative::Centroid;
     }
+    if matches.is_present("use_dba") {
+        let mut clustering_args = CLUSTERINGARGS.lock().unwrap();
+        clustering_args.cluster_strategy = EdgeRepresentative::Dba;
+    }
+
+    if let Some(segmentation) = matches.value_of("segmentation") {
+        let mut clustering_args = CLUSTERINGARGS.lock().unwrap();
+        clustering_args.segmentation = match segmentation {
+            "stops" => Segmentation::Stops,
+            _ => Segmentation::Timeout,
+        };
+    }
+
+    if let Some(builder) = matches.value_of("builder") {
+        let mut clustering_args = CLUSTERINGARGS.lock().unwrap();
+        clustering_args.builder = match builder {
+            "v1" => GraphBuilder::V1,
+            _ => GraphBuilder::V2,
+        };
+    }
+
+    if let Some(threads) = matches.value_of("threads") {
+        rayon::ThreadPoolBuilder::new()
+            .num_threads(
+                threads
+                    .parse()
+                    .expect("Number of threads must be an integer"),
+            )
+            .build_global()
+            .expect("Could not create thread pool");
+    }
+
+    if matches.is_present("vessels") {
+        let mut clustering_args = CLUSTERINGARGS.lock().unwrap();
+        clustering_args.vessels = true;
+    }
+    if matches.is_present("separate_modes") {
+        let mut clustering_args = CLUSTERINGARGS.lock().unwrap();
+        clustering_args.separate_modes = true;
+    }
+    if let Some(day_types) = matches.value_of("day_types") {
+        let mut clustering_args = CLUSTERINGARGS.lock().unwrap();
+        clustering_args.day_types = Some(match day_types {
+            "places" => DayClassification::Places,
+            _ => DayClassification::Weekend,
+        });
+    }
+    if let Some(holidays) = matches.value_of("holidays") {
+        let mut clustering_args = CLUSTERINGARGS.lock().unwrap();
+        if clustering_args.day_types.is_none() {
+            clustering_args.day_types = Some(DayClassification::Weekend);
+        }
+        let mut input = INPUT.lock().unwrap();
+        input.holidays = Some(fs::canonicalize(holidays).expect("Can't find holidays"));
+    }
+    if matches.is_present("weekly") {
+        let mut clustering_args = CLUSTERINGARGS.lock().unwrap();
+        clustering_args.weekly = true;
+    }
+    if matches.is_present("per_object") {
+        let mut clustering_args = CLUSTERINGARGS.lock().unwrap();
+        clustering_args.per_object = true;
+    }
 
     if matches.is_present("edges") {
         let mut output = OUTPUT.lock().unwrap();
@@ -135,6 +330,38 @@ pub fn parse_arguments() -> Config {
         let mut output = OUTPUT.lock().unwrap();
         output.graph_json = true;
     }
+    if matches.is_present("stats") {
+        let mut output = OUTPUT.lock().unwrap();
+        output.stats = true;
+    }
+    if matches.is_present("stays") {
+        let mut output = OUTPUT.lock().unwrap();
+        output.stays = true;
+    }
+    if matches.is_present("segmentation_debug") {
+        let mut output = OUTPUT.lock().unwrap();
+        output.segmentation_debug = true;
+    }
+
+    // Resolve input files before changing the working path
+    if let Some(files) = matches.values_of("input") {
+        let mut input = INPUT.lock().unwrap();
+        input.files = files
+            .map(|file| fs::canonicalize(file).expect("Can't find input file"))
+            .collect();
+    }
+    if let Some(osm) = matches.value_of("osm") {
+        let mut input = INPUT.lock().unwrap();
+        input.osm = Some(fs::canonicalize(osm).expect("Can't find OSM extract"));
+    }
+    if let Some(graph) = matches.value_of("extend") {
+        let mut input = INPUT.lock().unwrap();
+        input.graph = Some(fs::canonicalize(graph).expect("Can't find graph to extend"));
+    }
+    if let Some(graph) = matches.value_of("validate") {
+        let mut input = INPUT.lock().unwrap();
+        input.validate = Some(fs::canonicalize(graph).expect("Can't find graph to validate"));
+    }
 
     // NOTE: We set the working path here!
     if let Some(out_path) = matches.value_of("out_path") {
@@ -143,11 +370,12 @@ pub fn parse_arguments() -> Config {
         let out_path = Path::new(&out_path);
         assert!(env::set_current_dir(&out_path).is_ok());
     } else {
-        let output = OUTPUT.lock().unwrap();
-        if output.edges_csv | output.graph_dot | output.graph_json | output.nodes_csv {
+        if OUTPUT.lock().unwrap().is_enabled() {
             panic!("Please specify an output directory using -o <output_directory>");
         }
-        println!("Not writing any output. Use --help to get information on usage.");
+        if !matches.is_present("validate") {
+            println!("Not writing any output. Use --help to get information on usage.");
+        }
     }
     config
 }
@@ -157,6 +385,22 @@ pub struct Output {
     pub nodes_csv: bool,
     pub graph_json: bool,
     pub graph_dot: bool,
+    pub stats: bool,
+    pub stays: bool,
+    pub segmentation_debug: bool,
+}
+
+impl Output {
+    /// Returns true if any output is written.
+    pub fn is_enabled(&self) -> bool {
+        self.edges_csv
+            | self.graph_dot
+            | self.graph_json
+            | self.nodes_csv
+            | self.stats
+            | self.stays
+            | self.segmentation_debug
+    }
 }
 
 impl Default for Output {
@@ -166,19 +410,81 @@ impl Default for Output {
             nodes_csv: false,
             graph_json: false,
             graph_dot: false,
+            stats: false,
+            stays: false,
+            segmentation_debug: false,
         }
     }
 }
 
+/// Trajectory inserted for each cluster of edges instead of the trajectories of the cluster.
+#[derive(Clone, Copy, Debug, PartialEq, Eq, Serialize, Deserialize)]
+#[serde(rename_all = "snake_case")]
 pub enum EdgeRepresentative {
     Centroid,
     Medoid,
+    /// DTW barycenter average, see [dba](crate::utility::dba).
+    Dba,
+}
+
+/// Files to read input from. Input is read from stdin if there are none.
+#[derive(Default)]
+pub struct Input {
+    pub files: Vec<PathBuf>,
+    /// OpenStreetMap extract used for [map matching](crate::map_matching).
+    pub osm: Option<PathBuf>,
+    /// Graph to [extend](crate::graph::extend_graph) instead of building a new one.
+    pub graph: Option<PathBuf>,
+    /// Holidays used when [classifying days](crate::graph::classify_days).
+    pub holidays: Option<PathBuf>,
+    /// Graph to [validate](crate::graph::validate) instead of building one.
+    pub validate: Option<PathBuf>,
+}
+
+/// Determines where streams are cut into the trajectories used to build the graph.
+#[derive(Clone, Copy)]
+pub enum Segmentation {
+    /// Cut only when `connection_timeout` is exceeded.
+    Timeout,
+    /// Cut at stops found by the [stop detector](crate::StopDetector) as well,
+    /// s.t. nodes represent the places visited during the day.
+    Stops,
+}
+
+/// Selects how the graph is constructed.
+#[derive(Clone, Copy)]
+pub enum GraphBuilder {
+    /// Builds paths of stops and routes and merges spatially overlapping nodes, see [get_graph](crate::graph::get_graph).
+    V1,
+    /// Clusters the endpoints of trajectories, see [get_graph_v2](crate::graph::get_graph_v2).
+    V2,
+}
+
+/// Selects how the day type of each stream is determined, see [classify_days](crate::graph::classify_days).
+#[derive(Clone, Copy)]
+pub enum DayClassification {
+    /// Weekdays and weekends are of different types.
+    Weekend,
+    /// Days are clustered by the places visited.
+    Places,
 }
 
 pub struct ClusteringArgs {
     pub threshold: f64,
     pub temporal_slack: f64,
     pub cluster_strategy: EdgeRepresentative,
+    pub segmentation: Segmentation,
+    pub builder: GraphBuilder,
+    /// The input describes vessels, see [classify](crate::utility::transport_mode::classify).
+    pub vessels: bool,
+    /// Keeps trajectories of different transport modes in separate edges.
+    pub separate_modes: bool,
+    /// Timestamps are offset by the weekday of their stream, see [cyclic_time](crate::utility::cyclic_time).
+    pub weekly: bool,
+    /// Builds a graph for each day type instead of a single graph.
+    pub day_types: Option<DayClassification>,
+    /// Builds a graph for each object instead of a graph shared by all objects.
+    pub per_object: bool,
 }
 
 impl Default for ClusteringArgs {
@@ -187,6 +493,28 @@ impl Default for ClusteringArgs {
             threshold: 50.0,
             temporal_slack: 24.0 * 60.0 * 60.0 * 100.0,
             cluster_strategy: EdgeRepresentative::Medoid,
+            segmentation: Segmentation::Timeout,
+            builder: GraphBuilder::V2,
+            vessels: false,
+            separate_modes: false,
+            weekly: false,
+            day_types: None,
+            per_object: false,
         }
     }
 }
+
+#[cfg(test)]
+mod test {
+    use super::*;
+
+    #[test]
+    fn only_the_v2_builder_extends_graphs() {
+        let args =
+            |builder: &'static str| ["detour", "--builder", builder, "--extend", "graph.json"];
+        let err = get_matches(args("v1")).unwrap_err();
+        assert_eq!(err.kind(), ErrorKind::ArgumentConflict);
+        assert!(get_matches(args("v2")).is_ok());
+        assert!(get_matches(["detour", "--builder", "v1"]).is_ok());
+    }
+}
@@ -1,7 +1,11 @@
+use crate::arguments::EdgeRepresentative;
+use crate::utility::clustering::ClusteringMethod;
+use crate::utility::similarity::SimilarityMeasure;
 use serde::{Deserialize, Serialize};
 use std::fmt::Display;
 
 #[derive(Clone, Copy, Debug, Serialize, Deserialize)]
+#[serde(default)]
 pub struct Config {
     /// Number of points used in the CH-filter.
     pub window_size: usize,
@@ -19,8 +23,54 @@ pub struct Config {
     pub bbox_min_minutes: f64,
     /// If two trajectories belonging to the same edge have a Hausdorff distance of more than this, they will not be merged.
     pub max_hausdorff_meters: f64,
+    /// Measure used to compare the trajectories between two nodes when clustering them into edges.
+    pub edge_similarity: SimilarityMeasure,
+    /// If two trajectories have a discrete Fréchet distance of more than this, they will not be merged.
+    pub max_frechet_meters: f64,
+    /// If two trajectories have an average dynamic time warping cost of more than this, they will not be merged.
+    pub max_dtw_meters: f64,
+    /// If more than this fraction of the points must be edited to turn one trajectory into the other, they will not be merged.
+    pub max_edr: f64,
+    /// If less than one minus this fraction of the points belong to their longest common subsequence, two trajectories will not be merged.
+    pub max_lcss: f64,
+    /// If two trajectories are on average further apart than this during their common timespan, they will not be merged.
+    pub max_tradis_meters: f64,
+    /// Points further apart than this are not considered a match by EDR and LCSS.
+    pub similarity_epsilon_meters: f64,
+    /// Points recorded further apart in time than this many milliseconds are not considered a match by EDR and LCSS.
+    pub similarity_epsilon_ms: f64,
+    /// Trajectory representing each cluster of edges, unless overridden by `--use-centroids`, `--use-medoids` or `--use-dba`.
+    pub edge_representative: EdgeRepresentative,
+    /// Maximal number of refinements of the DTW barycenter average used as `dba` edge representative.
+    pub dba_iterations: usize,
+    /// Fraction of the points of the trajectories of an edge covered by its corridor.
+    pub corridor_percentile: f64,
+    /// Width in minutes of the bins of the histograms of travel durations written with `--edges`.
+    pub duration_bin_minutes: f64,
+    /// Width in minutes of the bins of the histograms of dwell times in nodes written with `--nodes`.
+    pub dwell_bin_minutes: f64,
+    /// Trajectories whose 95th percentile speed in km/h is below this are stationary. Objects slowing down below it halt.
+    pub stationary_km_h: f64,
     /// Threshold for Visvalingam algorithm.
     pub visvalingam_threshold: f64,
+    /// Standard deviation in meters of the GPS noise assumed when map matching.
+    pub map_matching_sigma: f64,
+    /// Scale in meters of the difference between route and straight line distances tolerated when map matching.
+    pub map_matching_beta: f64,
+    /// Measurements are only matched to roads within this many meters.
+    pub map_matching_radius: f64,
+    /// Method used to cluster trajectory endpoints into nodes, using `--clustering-threshold` as threshold.
+    pub endpoint_clustering: ClusteringMethod,
+    /// Method used to cluster the trajectories between two nodes into edges, using the threshold of the `edge_similarity` measure.
+    pub edge_clustering: ClusteringMethod,
+    /// Least number of neighbours, counting itself, of a core element when clustering using DBSCAN.
+    pub dbscan_min_points: usize,
+    /// Least number of elements in a cluster when clustering using HDBSCAN.
+    pub hdbscan_min_cluster_size: usize,
+    /// Side length of the grid cells used to compare the places visited on different days.
+    pub day_type_cell_meters: f64,
+    /// Days whose visited places have an average Jaccard distance of more than this are of different types.
+    pub day_type_max_jaccard: f64,
 }
 
 impl Default for Config {
@@ -34,7 +84,30 @@ impl Default for Config {
             bbox_max_meters: 50.0,
             bbox_min_minutes: 15.0,
             max_hausdorff_meters: 100.,
+            edge_similarity: SimilarityMeasure::Hausdorff,
+            max_frechet_meters: 150.0,
+            max_dtw_meters: 100.0,
+            max_edr: 0.3,
+            max_lcss: 0.3,
+            max_tradis_meters: 100.0,
+            similarity_epsilon_meters: 50.0,
+            similarity_epsilon_ms: 15.0 * 60.0 * 1000.0,
+            edge_representative: EdgeRepresentative::Medoid,
+            dba_iterations: 10,
+            corridor_percentile: 0.9,
+            duration_bin_minutes: 5.0,
+            dwell_bin_minutes: 30.0,
+            stationary_km_h: 3.0,
             visvalingam_threshold: 0.5,
+            map_matching_sigma: 10.0,
+            map_matching_beta: 30.0,
+            map_matching_radius: 50.0,
+            endpoint_clustering: ClusteringMethod::Single,
+            edge_clustering: ClusteringMethod::Single,
+            dbscan_min_points: 3,
+            hdbscan_min_cluster_size: 3,
+            day_type_cell_meters: 250.0,
+            day_type_max_jaccard: 0.5,
         }
     }
 }
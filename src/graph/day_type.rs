@@ -0,0 +1,156 @@
+use super::{get_stays, PathBuilderStats};
+use crate::arguments::DayClassification;
+use crate::parser::DailyStream;
+use crate::utility::clustering::{self, ClusteringMethod};
+use crate::utility::cyclic_time::{weekday_offset, MS_IN_24H};
+use crate::CONFIG;
+use itertools::Itertools;
+use std::collections::{BTreeMap, BTreeSet, HashSet};
+use std::io::Result;
+use std::path::Path;
+
+/// A cell of the grid used to compare the places visited on different days.
+type Place = (i64, i64);
+
+/// Day type of the streams recorded on a holiday.
+pub const HOLIDAY: &str = "holiday";
+
+/// Reads a list of holidays formatted as `YYYY-MM-DD`, one on each line.
+pub fn read_holidays(path: &Path) -> Result<HashSet<String>> {
+    Ok(std::fs::read_to_string(path)?
+        .lines()
+        .map(|line| line.trim().to_string())
+        .filter(|line| !line.is_empty())
+        .collect())
+}
+
+/// Returns the day type of each stream.
+///
+/// Streams recorded on one of the `holidays` are always of type [HOLIDAY].
+/// The remaining streams are either classified as `weekday` or `weekend`, or clustered by the places
+/// the object stayed at into types named `type_0`, `type_1` and so on.
+pub fn classify_days(
+    streams: &[DailyStream],
+    classification: DayClassification,
+    holidays: &HashSet<String>,
+) -> Vec<String> {
+    let mut day_types: Vec<Option<String>> = streams
+        .iter()
+        .map(|(id, _)| holidays.get(&id.day).map(|_| HOLIDAY.to_string()))
+        .collect();
+    let remaining: Vec<usize> = (0..streams.len())
+        .filter(|idx| day_types[*idx].is_none())
+        .collect();
+    match classification {
+        DayClassification::Weekend => {
+            for idx in remaining {
+                let day_type = match weekday_offset(&streams[idx].0.day) {
+                    Some(offset) if offset >= 5.0 * MS_IN_24H => "weekend",
+                    Some(_) => "weekday",
+                    None => "unknown",
+                };
+                day_types[idx] = Some(day_type.to_string());
+            }
+        }
+        DayClassification::Places => {
+            let places = remaining
+                .iter()
+                .map(|idx| visited_places(&streams[*idx].1))
+                .collect_vec();
+            for (idx, cluster) in remaining.iter().zip(cluster_by_places(&places)) {
+                day_types[*idx] = Some(format!("type_{}", cluster));
+            }
+        }
+    }
+    day_types.into_iter().map(|t| t.unwrap()).collect()
+}
+
+/// Groups the streams by their day type.
+pub fn group_by_day_type(
+    streams: Vec<DailyStream>,
+    day_types: Vec<String>,
+) -> BTreeMap<String, Vec<DailyStream>> {
+    let mut groups: BTreeMap<String, Vec<DailyStream>> = BTreeMap::new();
+    for (stream, day_type) in streams.into_iter().zip(day_types) {
+        groups.entry(day_type).or_default().push(stream);
+    }
+    groups
+}
+
+/// Returns the grid cells containing the stays of the stream.
+fn visited_places(stream: &[[f64; 3]]) -> BTreeSet<Place> {
+    let cell = |v: f64| (v / CONFIG.day_type_cell_meters).floor() as i64;
+    get_stays(stream, &mut PathBuilderStats::default())
+        .iter()
+        .map(|stay| {
+            let [x, y] = stay.center();
+            (cell(x), cell(y))
+        })
+        .collect()
+}
+
+/// Clusters days by the Jaccard distance between their visited places using average linkage.
+/// Returns the cluster of each day, where clusters are numbered by their first day.
+fn cluster_by_places(places: &[BTreeSet<Place>]) -> Vec<usize> {
+    let dists = places
+        .iter()
+        .map(|a| places.iter().map(|b| jaccard_distance(a, b)).collect())
+        .collect();
+    let mut clusters = clustering::cluster(
+        dists,
+        CONFIG.day_type_max_jaccard,
+        ClusteringMethod::Average,
+    );
+    clusters.sort_by_key(|c| c.iter().min().copied());
+    let mut labels = vec![0; places.len()];
+    for (label, cluster) in clusters.iter().enumerate() {
+        for idx in cluster {
+            labels[*idx] = label;
+        }
+    }
+    labels
+}
+
+fn jaccard_distance(a: &BTreeSet<Place>, b: &BTreeSet<Place>) -> f64 {
+    let union = a.union(b).count();
+    if union == 0 {
+        return 0.0;
+    }
+    1.0 - a.intersection(b).count() as f64 / union as f64
+}
+
+#[cfg(test)]
+mod test {
+    use super::*;
+    use crate::parser::StreamId;
+
+    fn id(day: &str) -> StreamId {
+        StreamId {
+            source: String::new(),
+            object: String::new(),
+            day: day.to_string(),
+        }
+    }
+
+    #[test]
+    fn classify_weekends_and_holidays() {
+        let streams = ["2023-01-06", "2023-01-07", "2023-01-08", "2023-01-09"]
+            .iter()
+            .map(|day| (id(day), vec![]))
+            .collect_vec();
+        let holidays = HashSet::from(["2023-01-09".to_string()]);
+        let day_types = classify_days(&streams, DayClassification::Weekend, &holidays);
+        assert_eq!(day_types, vec!["weekday", "weekend", "weekend", HOLIDAY]);
+        let groups = group_by_day_type(streams, day_types);
+        assert_eq!(groups["weekend"].len(), 2);
+    }
+
+    #[test]
+    fn cluster_days_by_visited_places() {
+        let workday = BTreeSet::from([(0, 0), (12, 0)]);
+        let other_workday = BTreeSet::from([(0, 0), (12, 0), (3, 4)]);
+        let trip = BTreeSet::from([(0, 0), (-40, 7), (-41, 7)]);
+        let places = vec![workday, trip, other_workday];
+        assert_eq!(cluster_by_places(&places), vec![0, 1, 0]);
+    }
+}
@@ -1,95 +1,141 @@
-use crate::utility::trajectory::merge;
-use crate::utility::{clustering, Bbox};
-use crate::{CONFIG, STATS};
-use clustering::Clustering;
+use crate::utility::clustering;
+use crate::utility::dba::dba;
+use crate::utility::trajectory::weighted_centroid;
+use crate::{CLUSTERINGARGS, CONFIG, STATS};
+use itertools::Itertools;
 use petgraph::graph::NodeIndex;
 use petgraph::prelude::EdgeIndex;
-use petgraph::stable_graph::StableDiGraph;
 use petgraph::visit::EdgeRef;
 use petgraph::EdgeDirection;
-use std::collections::HashMap;
-use trajectory_similarity::hausdorff;
+use rayon::prelude::*;
+use std::collections::BTreeMap;
 
-use super::{get_mediod_trj, Graph};
+use super::{get_mediod_trj, EdgeInfo, Graph, Spread};
 
 type EdgeClusters = Vec<Vec<EdgeIndex>>;
 
-/// Cluster the edges using Hausdorff similarity and insert a representative(mediod)
+/// Cluster the edges using the `edge_similarity` measure and insert a representative(mediod)
 /// trajectory for each cluster instead of the all of the original trajectories.
 pub fn set_edges_mediod_trjs(graph: &mut Graph) {
-    let groups: Vec<((NodeIndex, NodeIndex), EdgeClusters)> = get_edge_groups(graph)
-        .iter()
-        .map(|((source, target), group)| {
-            ((*source, *target), get_edge_group_clusters(graph, group))
-        })
-        .collect();
-    for ((source, target), clustering) in groups {
-        for cluster in clustering {
-            let weight = cluster.len() as u32;
-            let trj: Vec<[f64; 3]>;
-            {
-                let graph: &Graph = graph;
-                let mediod = get_mediod_trj(graph, &cluster);
-                let (_, mediod) = graph.edge_weight(mediod).unwrap();
-                trj = mediod.clone();
-            }
-            replace_edges(graph, source, target, &cluster, (weight, trj));
-        }
-    }
+    set_edges_representative(graph, |graph, cluster, _| {
+        let mediod = get_mediod_trj(graph, cluster);
+        (graph.edge_weight(mediod).unwrap().1.clone(), mediod)
+    });
 }
 
-/// Merges the edges using interpolation.
-/// I.e. the average/mean position of the moving object at a given time
-/// according to the two trajectories.
+/// Merges the edges of each cluster into their centroid,
+/// i.e. the mean position of the moving object at a given time weighted by the edge counts,
+/// see [weighted_centroid].
 pub fn set_edges_centroid_trjs(graph: &mut Graph) {
+    set_edges_representative(graph, |_, cluster, trjs| {
+        (weighted_centroid(trjs), cluster[0])
+    });
+}
+
+/// Merges the edges of each cluster into their DTW barycenter average weighted by the edge counts,
+/// starting from the medoid of the cluster, see [dba].
+pub fn set_edges_dba_trjs(graph: &mut Graph) {
+    set_edges_representative(graph, |graph, cluster, trjs| {
+        let mediod = &graph.edge_weight(get_mediod_trj(graph, cluster)).unwrap().1;
+        (dba(trjs, mediod, CONFIG.dba_iterations), cluster[0])
+    });
+}
+
+/// Clusters the edges between each pair of nodes and replaces each cluster by a single edge.
+///
+/// `representative` is given the cluster along with the trajectory of each edge weighted by its count,
+/// and returns the trajectory of the new edge along with the edge of the cluster whose matched ways it keeps.
+/// The new edge counts every trajectory of the cluster, as representatives of earlier runs count every
+/// trajectory they represent, and the information of all edges of the cluster is merged.
+fn set_edges_representative<F>(graph: &mut Graph, representative: F)
+where
+    F: Fn(&Graph, &[EdgeIndex], &[(f64, &[[f64; 3]])]) -> (Vec<[f64; 3]>, EdgeIndex),
+{
     let groups: Vec<((NodeIndex, NodeIndex), EdgeClusters)> = get_edge_groups(graph)
-        .iter()
+        .par_iter()
         .map(|((source, target), group)| {
             ((*source, *target), get_edge_group_clusters(graph, group))
         })
         .collect();
     for ((source, target), clustering) in groups {
         for cluster in clustering {
-            let mut trjs: Vec<(u32, Vec<[f64; 3]>)> = cluster
+            STATS.lock().unwrap().edge_merges += cluster.len() - 1;
+            let members: Vec<&(u32, Vec<[f64; 3]>, EdgeInfo)> = cluster
                 .iter()
-                .map(|ex| graph.edge_weight(*ex).unwrap().clone())
+                .map(|ex| graph.edge_weight(*ex).unwrap())
                 .collect();
-            STATS.lock().unwrap().edge_merges += if trjs.is_empty() { 0 } else { trjs.len() - 1 };
-            let trj = trjs.pop().unwrap();
-            let trj: (u32, Vec<[f64; 3]>) = trjs.into_iter().fold(trj, |trj_a, trj_b| {
-                (trj_a.0 + trj_b.0, merge(&trj_a.1, &trj_b.1))
-            });
-            replace_edges(graph, source, target, &cluster, trj);
+            let weight = members.iter().map(|(count, _, _)| count).sum::<u32>();
+            let trjs: Vec<(f64, &[[f64; 3]])> = members
+                .iter()
+                .map(|(count, trj, _)| (*count as f64, trj.as_slice()))
+                .collect();
+            let (trj, kept) = representative(graph, &cluster, &trjs);
+            let mut info = EdgeInfo {
+                ways: graph.edge_weight(kept).unwrap().2.ways.clone(),
+                ..EdgeInfo::default()
+            };
+            for (_, _, other) in members.iter() {
+                info.merge(other);
+            }
+            replace_edges(graph, source, target, &cluster, (weight, trj, info));
         }
     }
 }
 
-fn get_edge_group_clusters(
-    graph: &StableDiGraph<(u32, Bbox), (u32, Vec<[f64; 3]>)>,
-    group: &[EdgeIndex],
-) -> EdgeClusters {
+/// Clusters the edges of the group by the `edge_similarity` measure using the `edge_clustering` method.
+/// If `separate_modes` is set, edges with different dominant transport modes are never clustered together.
+/// Edges matched to the same ways of the road network are considered identical.
+/// The rows of the distance matrix are computed in parallel.
+fn get_edge_group_clusters(graph: &Graph, group: &[EdgeIndex]) -> EdgeClusters {
+    let separate_modes = CLUSTERINGARGS.lock().unwrap().separate_modes;
     let n = group.len();
-    let mut dists = vec![vec![0f64; n]; n];
-    for i in 0..group.len() {
-        for j in (i + 1)..group.len() {
+    let upper: Vec<Vec<f64>> = (0..n)
+        .into_par_iter()
+        .map(|i| {
             let trj_a = graph.edge_weight(group[i]).unwrap();
-            let trj_b = graph.edge_weight(group[j]).unwrap();
-            dists[i][j] = hausdorff::similarity(&trj_a.1, &trj_b.1);
-            dists[j][i] = dists[i][j];
+            ((i + 1)..n)
+                .map(|j| {
+                    let trj_b = graph.edge_weight(group[j]).unwrap();
+                    if separate_modes & (trj_a.2.dominant_mode() != trj_b.2.dominant_mode()) {
+                        f64::INFINITY
+                    } else if trj_a.2.same_route(&trj_b.2) {
+                        0.0
+                    } else {
+                        CONFIG.edge_similarity.distance(&trj_a.1, &trj_b.1)
+                    }
+                })
+                .collect()
+        })
+        .collect();
+    let mut dists = vec![vec![0f64; n]; n];
+    for (i, row) in upper.into_iter().enumerate() {
+        for (j, dist) in ((i + 1)..n).zip(row) {
+            dists[i][j] = dist;
+            dists[j][i] = dist;
         }
     }
-    let clusters = Clustering::new(dists, CONFIG.max_hausdorff_meters).clusters;
+    let clusters = clustering::cluster(
+        dists,
+        CONFIG.edge_similarity.threshold(),
+        CONFIG.edge_clustering,
+    );
+    // Keep the order of the group s.t. representatives are chosen deterministically
     let clusters = clusters
         .iter()
-        .map(|c| c.iter().map(|idx| group[*idx]).collect::<Vec<EdgeIndex>>())
+        .map(|c| {
+            c.iter()
+                .copied()
+                .sorted()
+                .map(|idx| group[idx])
+                .collect::<Vec<EdgeIndex>>()
+        })
         .collect::<EdgeClusters>();
     clusters
 }
 
-fn get_edge_groups(
-    graph: &StableDiGraph<(u32, Bbox), (u32, Vec<[f64; 3]>)>,
-) -> HashMap<(NodeIndex, NodeIndex), Vec<EdgeIndex>> {
-    let mut groups: HashMap<(NodeIndex, NodeIndex), Vec<EdgeIndex>> = HashMap::new();
+/// Returns the edges grouped by their endpoints, ordered by endpoints s.t. groups are processed deterministically.
+fn get_edge_groups(graph: &Graph) -> BTreeMap<(NodeIndex, NodeIndex), Vec<EdgeIndex>> {
+    let mut groups: BTreeMap<(NodeIndex, NodeIndex), Vec<EdgeIndex>> = BTreeMap::new();
     for source in graph.node_indices() {
         graph
             .edges_directed(source, EdgeDirection::Outgoing)
@@ -109,17 +155,28 @@ fn get_edge_groups(
     groups
 }
 
+/// Replaces the edges of the group by the representative `trj` and records their [spread](Spread) around it.
+///
+/// The representative is kept unsimplified, s.t. trajectories added when [extending](super::extend_graph)
+/// the graph are compared to its full geometry. It is only simplified when written to csv.
 fn replace_edges(
-    graph: &mut StableDiGraph<(u32, Bbox), (u32, Vec<[f64; 3]>)>,
+    graph: &mut Graph,
     source: NodeIndex,
     target: NodeIndex,
     group: &[EdgeIndex],
-    trj: (u32, Vec<[f64; 3]>),
+    trj: (u32, Vec<[f64; 3]>, EdgeInfo),
 ) {
+    let mut info = trj.2;
+    let members: Vec<(u32, &[[f64; 3]], &Spread)> = group
+        .iter()
+        .map(|ex| {
+            let (count, trj, info) = graph.edge_weight(*ex).unwrap();
+            (*count, trj.as_slice(), &info.spread)
+        })
+        .collect();
+    info.spread = Spread::new(&trj.1, &members);
     group.iter().for_each(|ex| {
         graph.remove_edge(*ex);
     });
-    // Simplify the trajectory to avoid an excessive amount of points.
-    let simplified = crate::visvalingam(&trj.1, CONFIG.visvalingam_threshold);
-    graph.add_edge(source, target, (trj.0, simplified));
+    graph.add_edge(source, target, (trj.0, trj.1, info));
 }
@@ -0,0 +1,238 @@
+use super::{Graph, Spread, Support, TravelTimes};
+use crate::map_matching::match_trajectory;
+use crate::parser::TrajectoryId;
+use crate::utility::csv_field;
+use crate::utility::transport_mode::{classify, TransportMode};
+use crate::ROAD_NETWORK;
+use itertools::Itertools;
+use petgraph::visit::{EdgeRef, IntoEdgeReferences};
+use serde::{Deserialize, Serialize};
+use std::collections::BTreeMap;
+use std::fs::File;
+use std::io::{BufWriter, Result, Write};
+use std::path::Path;
+
+/// Information about the trajectories an edge represents.
+#[derive(Clone, Debug, Default, Serialize, Deserialize)]
+pub struct EdgeInfo {
+    /// Number of trajectories of each transport mode.
+    pub modes: BTreeMap<TransportMode, u32>,
+    /// Ids of the OSM ways the edge follows. Empty unless a road network is given.
+    pub ways: Vec<i64>,
+    /// Spread of the trajectories around the representative of the edge.
+    #[serde(default)]
+    pub spread: Spread,
+    /// Departure and arrival times of the trajectories.
+    #[serde(default)]
+    pub times: TravelTimes,
+    /// The input trajectories the edge represents. Empty if the builder doesn't keep track of them.
+    #[serde(default)]
+    pub members: Vec<TrajectoryId>,
+}
+
+impl EdgeInfo {
+    /// Returns the information about a single trajectory, which is moved by a vessel if `vessels` is set.
+    pub fn new(trj: &[[f64; 3]], vessels: bool) -> Self {
+        let mut modes = BTreeMap::new();
+        modes.insert(classify(trj, vessels), 1);
+        let ways = ROAD_NETWORK
+            .as_ref()
+            .map(|network| match_trajectory(network, trj))
+            .unwrap_or_default();
+        EdgeInfo {
+            modes,
+            ways,
+            spread: Spread::default(),
+            times: TravelTimes::new(trj),
+            members: vec![],
+        }
+    }
+
+    /// Adds the information of `other` to `self`.
+    /// The ways of `self` are kept, unless it has none.
+    pub fn merge(&mut self, other: &EdgeInfo) {
+        for (mode, count) in other.modes.iter() {
+            *self.modes.entry(*mode).or_insert(0) += count;
+        }
+        self.times.merge(&other.times);
+        self.members.extend(other.members.iter().cloned());
+        if self.ways.is_empty() {
+            self.ways = other.ways.clone();
+        }
+    }
+
+    /// Returns how many distinct objects and days the trajectories of the edge come from.
+    pub fn support(&self) -> Support {
+        Support::new(self.members.iter().map(|id| &id.stream))
+    }
+
+    /// Returns true if both edges are matched to the same sequence of ways.
+    pub fn same_route(&self, other: &EdgeInfo) -> bool {
+        !self.ways.is_empty() & (self.ways == other.ways)
+    }
+
+    /// Returns the most frequent transport mode.
+    pub fn dominant_mode(&self) -> Option<TransportMode> {
+        self.modes
+            .iter()
+            .max_by_key(|(_, count)| **count)
+            .map(|(mode, _)| *mode)
+    }
+}
+
+/// Assigns each input trajectory to the edge representing it and the nodes it connects in `assignments.csv`.
+///
+/// Consecutive segments of a stream give the sequence of nodes and edges the stream traverses.
+pub fn write_assignments(graph: &Graph, folder: &Path) -> Result<()> {
+    let mut assignments = graph
+        .edge_references()
+        .enumerate()
+        .flat_map(|(i, edge)| {
+            edge.weight()
+                .2
+                .members
+                .iter()
+                .map(move |id| (id, edge.source().index(), i, edge.target().index()))
+        })
+        .collect_vec();
+    assignments.sort();
+    let assignments = assignments
+        .into_iter()
+        .map(|(id, source, edge, target)| {
+            format!(
+                "{},{},{},{},{},{},{}",
+                csv_field(&id.stream.source),
+                csv_field(&id.stream.object),
+                id.stream.day,
+                id.segment,
+                source,
+                edge,
+                target
+            )
+        })
+        .join("\n");
+    let f = File::create(folder.join("assignments.csv"))?;
+    let mut f = BufWriter::new(f);
+    writeln!(
+        f,
+        "source,object,day,segment,source_node,edge,target_node\n{}",
+        assignments
+    )?;
+    Ok(())
+}
+
+#[cfg(test)]
+mod test {
+    use super::*;
+    use crate::graph::temporal_splitting::make_temporally_monotone;
+    use crate::graph::{get_graph_v2, NodeInfo};
+    use crate::parser::StreamId;
+    use crate::utility::Bbox;
+
+    const MINUTE: f64 = 60.0 * 1000.0;
+
+    /// A day of commuting 3km between home and work at the same times every day, sampled every minute,
+    /// with a gap of two hours at work if `gap` is set.
+    fn commute(gap: bool) -> Vec<[f64; 3]> {
+        let mut t = 7.0 * 60.0 * MINUTE;
+        let mut stream = vec![];
+        let go = |stream: &mut Vec<[f64; 3]>, t: &mut f64, from: f64, to: f64, minutes: usize| {
+            for i in 0..minutes {
+                let x = from + (to - from) * i as f64 / minutes as f64;
+                stream.push([x + (i % 5) as f64, 0.0, *t]);
+                *t += MINUTE;
+            }
+        };
+        go(&mut stream, &mut t, 0.0, 0.0, 60);
+        go(&mut stream, &mut t, 0.0, 3000.0, 30);
+        go(&mut stream, &mut t, 3000.0, 3000.0, 60);
+        if gap {
+            t += 120.0 * MINUTE;
+        }
+        go(&mut stream, &mut t, 3000.0, 3000.0, 60);
+        go(&mut stream, &mut t, 3000.0, 0.0, 30);
+        go(&mut stream, &mut t, 0.0, 0.0, 60);
+        stream
+    }
+
+    /// Returns the trajectories in the rows of `assignments.csv`, written to a folder named by `name`.
+    fn assigned(graph: &Graph, name: &str) -> Vec<TrajectoryId> {
+        let folder = std::env::temp_dir().join(format!("detour-{}-{}", name, std::process::id()));
+        std::fs::create_dir_all(&folder).unwrap();
+        write_assignments(graph, &folder).unwrap();
+        let csv = std::fs::read_to_string(folder.join("assignments.csv")).unwrap();
+        std::fs::remove_dir_all(&folder).unwrap();
+        csv.lines()
+            .skip(1)
+            .map(|row| {
+                let fields: Vec<&str> = row.split(',').collect();
+                TrajectoryId {
+                    stream: StreamId {
+                        source: fields[0].to_string(),
+                        object: fields[1].to_string(),
+                        day: fields[2].to_string(),
+                    },
+                    segment: fields[3].parse().unwrap(),
+                }
+            })
+            .collect()
+    }
+
+    #[test]
+    fn assign_every_trajectory_once_after_merging() {
+        // A single trajectory on the first days, and two separated by the gap on the later days
+        let streams: Vec<(StreamId, Vec<[f64; 3]>)> = (0..5)
+            .map(|day| {
+                let id = StreamId {
+                    day: format!("2023-01-0{}", day + 2),
+                    ..Default::default()
+                };
+                (id, commute(day >= 3))
+            })
+            .collect();
+        let ids: Vec<TrajectoryId> = streams
+            .iter()
+            .enumerate()
+            .flat_map(|(day, (id, _))| {
+                let segments = if day >= 3 { 2 } else { 1 };
+                (0..segments).map(move |segment| TrajectoryId {
+                    stream: id.clone(),
+                    segment,
+                })
+            })
+            .collect();
+        let graph = get_graph_v2(streams);
+        // Representatives merge the trajectories of the days
+        assert!(graph.edge_count() < ids.len());
+        assert_eq!(assigned(&graph, "merged"), ids);
+    }
+
+    #[test]
+    fn assign_every_trajectory_once_after_temporal_splitting() {
+        // A jog from a and a round trip from a to b, which both split a
+        let mut graph = Graph::new();
+        let bbox = |x: f64, t1: f64, t2: f64| Bbox::new(&[[x, 0.0, t1], [x + 10.0, 10.0, t2]]);
+        let a = graph.add_node((3, bbox(0.0, 0.0, 100.0), NodeInfo::default()));
+        let b = graph.add_node((2, bbox(500.0, 40.0, 70.0), NodeInfo::default()));
+        let id = |segment| TrajectoryId {
+            stream: StreamId::default(),
+            segment,
+        };
+        let mut edge = |from, to, trj: Vec<[f64; 3]>, segment| {
+            let mut info = EdgeInfo::new(&trj, false);
+            info.members.push(id(segment));
+            graph.add_edge(from, to, (1, trj, info));
+        };
+        edge(
+            a,
+            a,
+            vec![[5.0, 5.0, 5.0], [300.0, 5.0, 7.0], [5.0, 5.0, 9.0]],
+            0,
+        );
+        edge(a, b, vec![[5.0, 5.0, 10.0], [505.0, 5.0, 50.0]], 1);
+        edge(b, a, vec![[505.0, 5.0, 60.0], [5.0, 5.0, 90.0]], 2);
+        make_temporally_monotone(&mut graph);
+        assert_eq!(graph.node_count(), 4);
+        assert_eq!(assigned(&graph, "split"), vec![id(0), id(1), id(2)]);
+    }
+}
@@ -1,7 +1,11 @@
-use super::Path;
+use super::{
+    write_assignments, write_corridors, write_edge_times, write_node_visits, write_visits,
+    Distribution, EdgeInfo, NodeInfo, Path,
+};
 use crate::utility::trajectory::Monotone;
-use crate::utility::Bbox;
-use crate::{CONFIG, OUTPUT, STATS};
+use crate::utility::transport_mode::TransportMode;
+use crate::utility::{visvalingam, Bbox};
+use crate::{CLUSTERINGARGS, CONFIG, OUTPUT, STATS};
 use itertools::Itertools;
 use petgraph::dot::Dot;
 use petgraph::graph::NodeIndex;
@@ -18,7 +22,7 @@ struct TimePairs {
     b: bool,
 }
 
-pub type Graph = StableDiGraph<(u32, Bbox), (u32, Vec<[f64; 3]>)>;
+pub type Graph = StableDiGraph<(u32, Bbox, NodeInfo), (u32, Vec<[f64; 3]>, EdgeInfo)>;
 
 #[derive(Clone)]
 pub struct DetourGraph {
@@ -27,65 +31,205 @@ pub struct DetourGraph {
 }
 
 pub trait Writable {
-    fn to_csv(&self) -> Result<()>;
-}
+    /// Returns the graph to write.
+    fn as_graph(&self) -> &Graph;
 
-impl Writable for Graph {
+    /// Writes the graph to the output folder.
+    /// Command line arguments specifies the type of output.
+    /// Coordinates are written in Web Mercator (EPSG 3857) Projection.
     fn to_csv(&self) -> Result<()> {
+        self.to_csv_in(std::path::Path::new("."))
+    }
+
+    /// Writes the graph to `folder`, relative to the output folder, see [to_csv](Writable::to_csv).
+    fn to_csv_in(&self, folder: &std::path::Path) -> Result<()> {
+        let graph = self.as_graph();
         let output = OUTPUT.lock().unwrap();
 
         if output.graph_dot {
             // Store the graph in graphviz format
             let dot = Dot::with_config(
-                &self,
+                graph,
                 &[
                     petgraph::dot::Config::NodeIndexLabel,
                     petgraph::dot::Config::EdgeIndexLabel,
                 ],
             );
-            let f = File::create("graph.dot")?;
+            let f = File::create(folder.join("graph.dot"))?;
             let mut f = BufWriter::new(f);
             writeln!(f, "{:?}", dot)?;
         }
 
         if output.graph_json {
             // Store the graph in json format
-            let serialized = serde_json::to_string(self)?;
-            let mut f = File::create("graph.json")?;
+            let serialized = serde_json::to_string(graph)?;
+            let mut f = File::create(folder.join("graph.json"))?;
             f.write_all(serialized.as_bytes())?;
         }
 
         if output.nodes_csv {
-            // Write a single csv file with bounding boxes
-            let nodes = self
-                .node_indices()
-                .map(|nx| format!("{},{},{}", nx.index(), self[nx].0, self[nx].1))
-                .join("");
-            let nodes = format!("label,weight,x1,y1,t1,x2,y2,t2\n{}", nodes);
-            let f = File::create("nodes.csv")?;
-            let mut f = BufWriter::new(f);
-            writeln!(f, "{}", nodes)?;
+            write_nodes(graph, folder)?;
+            write_node_visits(graph, folder)?;
+            write_visits(graph, folder)?;
         }
 
         if output.edges_csv {
-            //println!("Writing {} edges", self.edge_count());
             // Write each trajectory to a separate csv file.
-            for (i, edge) in self.edge_references().enumerate() {
-                let f = File::create(format!("edge_{}_{}.csv", i, edge.weight().0))?;
-                let trj = edge
-                    .weight()
-                    .1
+            // Simplify the trajectory to avoid an excessive amount of points.
+            for (i, edge) in graph.edge_references().enumerate() {
+                let f = File::create(folder.join(format!("edge_{}_{}.csv", i, edge.weight().0)))?;
+                let trj = visvalingam(&edge.weight().1, CONFIG.visvalingam_threshold)
                     .iter()
                     .map(|[x, y, t]| format!("{},{},{}", x, y, t))
                     .join("\n");
                 let mut f = BufWriter::new(f);
                 write!(f, "x,y,t\n{}", trj)?;
             }
+            write_edges(graph, folder)?;
+            write_edge_times(graph, folder)?;
+            write_assignments(graph, folder)?;
+            write_corridors(graph, folder)?;
+        }
+
+        if output.stats {
+            write_stats(graph, folder)?;
         }
         Ok(())
     }
 }
 
+/// Writes the bounding boxes of the nodes and the distinct objects and days visiting each node to `nodes.csv`.
+fn write_nodes(graph: &Graph, folder: &std::path::Path) -> Result<()> {
+    let nodes = graph
+        .node_indices()
+        .map(|nx| {
+            let (weight, bbox, info) = &graph[nx];
+            let place = info.place.map(|p| p.to_string()).unwrap_or_default();
+            let support = info.support();
+            format!(
+                "{},{},{},{},{},{}",
+                nx.index(),
+                weight,
+                bbox.to_string().trim_end(),
+                place,
+                support.objects,
+                support.days
+            )
+        })
+        .join("\n");
+    let nodes = format!(
+        "label,weight,x1,y1,t1,x2,y2,t2,place,objects,days\n{}",
+        nodes
+    );
+    let f = File::create(folder.join("nodes.csv"))?;
+    let mut f = BufWriter::new(f);
+    writeln!(f, "{}", nodes)?;
+    Ok(())
+}
+
+/// Summarizes the transport modes, matched ways, spread, travel times and support of each edge in `edges.csv`.
+///
+/// Way ids are separated by spaces.
+fn write_edges(graph: &Graph, folder: &std::path::Path) -> Result<()> {
+    let edges = graph
+        .edge_references()
+        .enumerate()
+        .map(|(i, edge)| {
+            let info = &edge.weight().2;
+            let dominant = info
+                .dominant_mode()
+                .map(|mode| mode.to_string())
+                .unwrap_or_default();
+            let counts = TransportMode::ALL
+                .iter()
+                .map(|mode| info.modes.get(mode).unwrap_or(&0))
+                .join(",");
+            let support = info.support();
+            format!(
+                "{},{},{},{},{},{},{},{},{},{},{},{},{},{}",
+                i,
+                edge.source().index(),
+                edge.target().index(),
+                edge.weight().0,
+                dominant,
+                counts,
+                info.ways.iter().join(" "),
+                info.spread.mean_meters,
+                info.spread.percentile_meters,
+                median(&info.times.departures),
+                median(&info.times.arrivals),
+                median(&info.times.durations()),
+                support.objects,
+                support.days
+            )
+        })
+        .join("\n");
+    let header = TransportMode::ALL.iter().join(",");
+    let f = File::create(folder.join("edges.csv"))?;
+    let mut f = BufWriter::new(f);
+    writeln!(
+        f,
+        "label,source,target,weight,mode,{},ways,spread_mean,spread_percentile,departure,arrival,duration,objects,days\n{}",
+        header, edges
+    )?;
+    Ok(())
+}
+
+/// Writes the statistics gathered while building the graph to `stats.json`, along with the size of the graph.
+fn write_stats(graph: &Graph, folder: &std::path::Path) -> Result<()> {
+    let mut stats = serde_json::to_value(&*STATS.lock().unwrap())?;
+    stats["nodes"] = graph.node_count().into();
+    stats["edges"] = graph.edge_count().into();
+    let mut f = File::create(folder.join("stats.json"))?;
+    f.write_all(stats.to_string().as_bytes())?;
+    Ok(())
+}
+
+/// Returns the median of the times, or nothing if there are none.
+fn median(times: &[f64]) -> String {
+    Distribution::new(times, f64::INFINITY)
+        .map(|d| d.median.to_string())
+        .unwrap_or_default()
+}
+
+/// Graph written to `graph.json` before nodes and edges had their [NodeInfo] and [EdgeInfo].
+type LegacyGraph = StableDiGraph<(u32, Bbox), (u32, Vec<[f64; 3]>)>;
+
+/// Reads a graph previously written to `graph.json`, see [parse_graph].
+pub fn read_graph(path: &std::path::Path) -> Result<Graph> {
+    let contents = std::fs::read_to_string(path)?;
+    parse_graph(&contents)
+}
+
+/// Parses a graph in json format.
+///
+/// Graphs written by earlier versions, whose weights lack the information of nodes and edges,
+/// are read with empty information, s.t. their counts and geometry can still be extended or validated.
+fn parse_graph(contents: &str) -> Result<Graph> {
+    match serde_json::from_str(contents) {
+        Ok(graph) => Ok(graph),
+        Err(err) => {
+            let legacy: LegacyGraph = serde_json::from_str(contents).map_err(|_| err)?;
+            Ok(legacy.map(
+                |_, (weight, bbox)| (*weight, *bbox, NodeInfo::default()),
+                |_, (count, trj)| (*count, trj.clone(), EdgeInfo::default()),
+            ))
+        }
+    }
+}
+
+impl Writable for Graph {
+    fn as_graph(&self) -> &Graph {
+        self
+    }
+}
+
+impl Writable for DetourGraph {
+    fn as_graph(&self) -> &Graph {
+        &self.graph
+    }
+}
+
 impl DetourGraph {
     pub fn new() -> DetourGraph {
         let graph: Graph = StableDiGraph::new();
@@ -108,76 +252,6 @@ impl DetourGraph {
         &self.graph
     }
 
-    /// Writes the graph to the output folder.
-    /// Command line arguments specifies the type of output.
-    /// Coordinates are written in Web Mercator (EPSG 3857) Projection.
-    pub fn to_csv(&self) -> Result<()> {
-        // //println!("Writing data...");
-        let output = OUTPUT.lock().unwrap();
-
-        if output.graph_dot {
-            // Store the graph in graphviz format
-            let dot = Dot::with_config(
-                &self.graph,
-                &[
-                    petgraph::dot::Config::NodeIndexLabel,
-                    petgraph::dot::Config::EdgeIndexLabel,
-                ],
-            );
-            let f = File::create("graph.dot")?;
-            let mut f = BufWriter::new(f);
-            writeln!(f, "{:?}", dot)?;
-        }
-
-        if output.graph_json {
-            // Store the graph in json format
-            let serialized = serde_json::to_string(&self.graph)?;
-            let mut f = File::create("graph.json")?;
-            f.write_all(serialized.as_bytes())?;
-        }
-
-        if output.nodes_csv {
-            // Write a single csv file with bounding boxes
-            let nodes = self
-                .graph
-                .node_indices()
-                .map(|nx| format!("{},{},{}", nx.index(), self.graph[nx].0, self.graph[nx].1))
-                .join("");
-            let nodes = format!("label,weight,x1,y1,t1,x2,y2,t2\n{}", nodes);
-            let f = File::create("nodes.csv")?;
-            let mut f = BufWriter::new(f);
-            writeln!(f, "{}", nodes)?;
-        }
-
-        if output.edges_csv {
-            println!("Writing {} edges", self.graph.edge_count());
-            // Write each trajectory to a separate csv file.
-            for (i, edge) in self.graph.edge_references().enumerate() {
-                let f = File::create(format!("edge_{}_{}.csv", i, edge.weight().0))?;
-                let trj = edge
-                    .weight()
-                    .1
-                    .iter()
-                    .map(|[x, y, t]| format!("{},{},{}", x, y, t))
-                    .join("\n");
-                let mut f = BufWriter::new(f);
-                write!(f, "x,y,t\n{}", trj)?;
-            }
-        }
-
-        // // Write Statistics
-        // println!("Storing stats:\n{:?}", *STATS.lock().unwrap());
-        // let f = File::create("stats")?;
-        // let mut f = BufWriter::new(f);
-        // write!(f, "{:?}", *STATS)?;
-
-        // // Write Configuration
-        // let f = File::create("config")?;
-        // let mut f = BufWriter::new(f);
-        // write!(f, "{}", *CONFIG)?;
-        Ok(())
-    }
-
     /// Removes node from the graph and the root list.
     pub fn remove_node(&mut self, nx: NodeIndex) {
         STATS.lock().unwrap().outlier_node_removals += 1;
@@ -196,15 +270,15 @@ impl DetourGraph {
     }
 
     /// Allows iteration over nodes.
-    pub fn node_indices(&self) -> petgraph::stable_graph::NodeIndices<(u32, Bbox)> {
+    pub fn node_indices(&self) -> petgraph::stable_graph::NodeIndices<'_, (u32, Bbox, NodeInfo)> {
         self.graph.node_indices()
     }
 
     /// Allows iteration over edge weights.
-    pub fn edge_weights(&self) -> Vec<&(u32, Vec<[f64; 3]>)> {
+    pub fn edge_weights(&self) -> Vec<&(u32, Vec<[f64; 3]>, EdgeInfo)> {
         self.graph
             .edge_weights()
-            .collect::<Vec<&(u32, Vec<[f64; 3]>)>>()
+            .collect::<Vec<&(u32, Vec<[f64; 3]>, EdgeInfo)>>()
     }
 
     pub fn get_node_bbox(&self, nx: NodeIndex) -> Bbox {
@@ -338,12 +412,14 @@ impl DetourGraph {
     }
 
     pub fn add_path(&mut self, mut path: Path) {
+        let vessels = CLUSTERINGARGS.lock().unwrap().vessels;
         let bbox = path.remove_first().copy_bbox().unwrap();
-        let mut a: NodeIndex = self.graph.add_node((1, bbox));
+        let mut a: NodeIndex = self.graph.add_node((1, bbox, NodeInfo::default()));
         self.roots.push(a);
         while let Some((trj, bbox)) = path.next_trj_stop() {
-            let b = self.graph.add_node((1, bbox));
-            self.graph.add_edge(a, b, (1, trj));
+            let b = self.graph.add_node((1, bbox, NodeInfo::default()));
+            let info = EdgeInfo::new(&trj, vessels);
+            self.graph.add_edge(a, b, (1, trj, info));
             a = b;
         }
         assert!(self.verify_constraints(), "Invalid graph structure!");
@@ -353,47 +429,72 @@ impl DetourGraph {
 #[cfg(test)]
 mod test {
     use super::*;
-    use crate::{config::Config, graph::path_builder::PathElement};
+    use crate::graph::PathElement;
+
+    fn path_of(elements: Vec<PathElement>) -> Path {
+        let mut path = Path::new();
+        elements.into_iter().for_each(|elm| path.push(elm));
+        path
+    }
+
+    #[test]
+    fn parse_legacy_graph() {
+        let mut legacy = LegacyGraph::default();
+        let a = legacy.add_node((2, Bbox::new(&[[0., 0., 0.], [1., 1., 1.]])));
+        let removed = legacy.add_node((1, Bbox::new(&[[8., 8., 8.]])));
+        let b = legacy.add_node((2, Bbox::new(&[[4., 4., 4.], [5., 5., 5.]])));
+        legacy.remove_node(removed);
+        legacy.add_edge(a, b, (2, vec![[0.5, 0.5, 0.5], [4.5, 4.5, 4.5]]));
+        let graph = parse_graph(&serde_json::to_string(&legacy).unwrap()).unwrap();
+        assert_eq!(graph.node_count(), 2);
+        assert_eq!(graph[b].0, 2);
+        assert_eq!(graph[b].2, NodeInfo::default());
+        let ex = graph.find_edge(a, b).unwrap();
+        assert_eq!(graph[ex].0, 2);
+        assert_eq!(graph[ex].1.len(), 2);
+        assert!(graph[ex].2.members.is_empty());
+
+        // Graphs in the current format are read as they were written
+        let graph = parse_graph(&serde_json::to_string(&graph).unwrap()).unwrap();
+        assert_eq!(graph.edge_count(), 1);
+    }
+
     #[test]
     fn merge_twins() {
-        let config = Config::default();
-        let mut graph = DetourGraph::new(&config);
+        let mut graph = DetourGraph::new();
         let bbox1 = Bbox::new(&[[0., 0., 0.], [1., 1., 1.]]);
         let bbox2 = Bbox::new(&[[2., 2., 2.], [3., 3., 3.]]);
         let bbox3 = Bbox::new(&[[4., 4., 4.], [5., 5., 5.]]);
         let trj = vec![[0.5, 0.5, 0.5], [2.5, 2.5, 2.5]];
         let trj2 = vec![[2.5, 2.5, 2.5], [4.5, 4.5, 4.5]];
-        let path: Path = vec![
+        let path = path_of(vec![
             PathElement::Stop(bbox1),
             PathElement::Route(trj),
             PathElement::Stop(bbox2),
             PathElement::Route(trj2),
             PathElement::Stop(bbox3),
-        ];
+        ]);
         graph.add_path(path.clone());
         graph.add_path(path);
-        let a = graph.roots[0];
-        let b = graph.roots[1];
-        graph.merge_node_pair(a, b);
+        assert_eq!(graph.roots.len(), 2);
         assert!(graph.verify_constraints());
     }
 
     #[test]
     fn merge_quadruplets() {
-        let config = Config::default();
-        let mut graph = DetourGraph::new(&config);
+        let mut graph = DetourGraph::new();
         let bbox1 = Bbox::new(&[[0., 0., 0.], [1., 1., 1.]]);
         let bbox2 = Bbox::new(&[[2., 2., 2.], [3., 3., 3.]]);
         let bbox3 = Bbox::new(&[[4., 4., 4.], [5., 5., 5.]]);
         let trj = vec![[0.5, 0.5, 0.5], [2.5, 2.5, 2.5]];
         let trj2 = vec![[2.5, 2.5, 2.5], [4.5, 4.5, 4.5]];
-        let path1: Path = vec![
+        let path1 = path_of(vec![
             PathElement::Stop(bbox1),
             PathElement::Route(trj),
             PathElement::Stop(bbox2),
             PathElement::Route(trj2),
             PathElement::Stop(bbox3),
-        ];
+        ]);
         graph.add_path(path1.clone());
         graph.add_path(path1.clone());
         graph.add_path(path1.clone());
@@ -403,8 +504,7 @@ mod test {
 
     #[test]
     fn merge_root_non_root() {
-        let config = Config::default();
-        let mut graph = DetourGraph::new(&config);
+        let mut graph = DetourGraph::new();
         let bbox1 = Bbox::new(&[[0., 0., 0.], [1., 1., 1.]]);
         let bbox2 = Bbox::new(&[[2., 2., 2.], [3., 3., 3.]]);
         let bbox3 = Bbox::new(&[[4., 4., 4.], [5., 5., 5.]]);
@@ -412,20 +512,20 @@ mod test {
         let trj = vec![[0.5, 0.5, 0.5], [2.5, 2.5, 2.5]];
         let trj2 = vec![[2.5, 2.5, 2.5], [4.5, 4.5, 4.5]];
         let trj3 = vec![[1.5, 1.5, 1.5], [2.5, 2.5, 2.5]];
-        let path1: Path = vec![
+        let path1 = path_of(vec![
             PathElement::Stop(bbox1),
             PathElement::Route(trj),
             PathElement::Stop(bbox2),
             PathElement::Route(trj2.clone()),
             PathElement::Stop(bbox3),
-        ];
-        let path2: Path = vec![
+        ]);
+        let path2 = path_of(vec![
             PathElement::Stop(bbox4),
             PathElement::Route(trj3),
             PathElement::Stop(bbox2),
             PathElement::Route(trj2),
             PathElement::Stop(bbox3),
-        ];
+        ]);
         graph.add_path(path1);
         graph.add_path(path2);
         assert!(graph.verify_constraints());
@@ -433,26 +533,25 @@ mod test {
 
     #[test]
     fn merge_skip_one() {
-        let config = Config::default();
-        let mut graph = DetourGraph::new(&config);
+        let mut graph = DetourGraph::new();
         let bbox1 = Bbox::new(&[[0., 0., 0.], [1., 1., 1.]]);
         let bbox2 = Bbox::new(&[[2., 2., 2.], [3., 3., 3.]]);
         let bbox3 = Bbox::new(&[[4., 4., 4.], [5., 5., 5.]]);
         let trj = vec![[0.5, 0.5, 0.5], [2.5, 2.5, 2.5]];
         let trj2 = vec![[2.5, 2.5, 2.5], [4.5, 4.5, 4.5]];
         let trj3 = vec![[1.5, 1.5, 1.5], [2.5, 2.5, 2.5]];
-        let path1: Path = vec![
+        let path1 = path_of(vec![
             PathElement::Stop(bbox1),
             PathElement::Route(trj),
             PathElement::Stop(bbox2),
             PathElement::Route(trj2),
             PathElement::Stop(bbox3),
-        ];
-        let path2: Path = vec![
+        ]);
+        let path2 = path_of(vec![
             PathElement::Stop(bbox1),
             PathElement::Route(trj3),
             PathElement::Stop(bbox3),
-        ];
+        ]);
         graph.add_path(path2);
         graph.add_path(path1.clone());
         graph.add_path(path1);
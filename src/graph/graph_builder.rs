@@ -1,7 +1,13 @@
-use super::set_edges_mediod_trjs;
-use super::{path_builder::get_paths, set_edges_centroid_trjs, DetourGraph, PathBuilderStats};
+use super::{
+    path_builder::get_paths, set_edges_centroid_trjs, DetourGraph, EdgeInfo, Graph, NodeInfo,
+    PathBuilderStats,
+};
+use super::{set_edges_dba_trjs, set_edges_mediod_trjs};
+use crate::arguments::EdgeRepresentative;
 use crate::graph::node_clustering::spatially_cluster_nodes;
+use crate::parser::StreamId;
 use crate::utility::Bbox;
+use crate::{CLUSTERINGARGS, STATS};
 use itertools::Itertools;
 use petgraph::graph::NodeIndex;
 use petgraph::stable_graph::StableDiGraph;
@@ -9,28 +15,37 @@ use petgraph::visit::EdgeRef;
 use petgraph::EdgeDirection;
 use std::collections::HashSet;
 
-type Graph = StableDiGraph<(u32, Bbox), (u32, Vec<[f64; 3]>)>;
-
 /// Constructs the graph
 /// Handles spatial node clustering, edge clustering and detecting less frequently visited places.
-pub fn get_graph(streams: Vec<Vec<[f64; 3]>>) -> DetourGraph {
+/// Visits to the nodes are not recorded.
+pub fn get_graph(streams: Vec<(StreamId, Vec<[f64; 3]>)>) -> DetourGraph {
     let mut graph = DetourGraph::new();
     let mut path_stats = PathBuilderStats::default();
     streams
         .into_iter()
-        .flat_map(|stream| get_paths(stream, &mut path_stats))
+        .flat_map(|(_, stream)| get_paths(stream, &mut path_stats))
         .filter(|path| path.len() > 1)
         .for_each(|path| graph.add_path(path));
     merge_nodes(&mut graph);
     //merge_edges(graph.get_mut_graph());
-    set_edges_mediod_trjs(graph.get_mut_graph());
+    let cluster_strategy = CLUSTERINGARGS.lock().unwrap().cluster_strategy;
+    match cluster_strategy {
+        EdgeRepresentative::Medoid => set_edges_mediod_trjs(graph.get_mut_graph()),
+        EdgeRepresentative::Centroid => set_edges_centroid_trjs(graph.get_mut_graph()),
+        EdgeRepresentative::Dba => set_edges_dba_trjs(graph.get_mut_graph()),
+    }
     graph
 }
 
 fn merge_nodes(graph: &mut DetourGraph) {
     let node_clustering = spatially_cluster_nodes(graph);
+    {
+        let mut stats = STATS.lock().unwrap();
+        stats.spatial_clusters += node_clustering.len();
+        stats.node_merges += graph.node_indices().count() - node_clustering.len();
+    }
     // calculate a cluster representative for each cluster
-    let representatives: Vec<(u32, Bbox)> = node_clustering
+    let representatives: Vec<(u32, Bbox, NodeInfo)> = node_clustering
         .iter()
         .map(|cluster| {
             let (weight, bbox) = (cluster.len() as u32, graph.get_graph()[cluster[0]].1);
@@ -42,7 +57,7 @@ fn merge_nodes(graph: &mut DetourGraph) {
                 t1: 0.0,                          // Time starts at 0.0
                 t2: (24 * 60 * 60 * 1000) as f64, // We always have 24*60*60*1000 ms in a 24 hour span
             };
-            (weight, bbox)
+            (weight, bbox, NodeInfo::default())
         })
         .collect();
 
@@ -50,7 +65,7 @@ fn merge_nodes(graph: &mut DetourGraph) {
     let mut new_graph: Graph = StableDiGraph::new();
     let representatives: Vec<NodeIndex> = representatives
         .iter()
-        .map(|bbox| new_graph.add_node(*bbox))
+        .map(|node| new_graph.add_node(node.clone()))
         .collect_vec();
 
     // Add edges to the cluster representatives
@@ -72,7 +87,7 @@ fn merge_nodes(graph: &mut DetourGraph) {
             }
         }
         if let (Some(a), Some(b)) = (start_node, end_node) {
-            new_graph.add_edge(a, b, (1, edge.1.clone()));
+            new_graph.add_edge(a, b, (1, edge.1.clone(), edge.2.clone()));
         }
     });
 
@@ -84,23 +99,27 @@ fn merge_nodes(graph: &mut DetourGraph) {
     let mut required_splits = vec![];
     for nx in new_graph.node_indices() {
         // calculate required splits
-        let mut edges: Vec<(u32, Vec<[f64; 3]>)> = new_graph
+        let mut edges: Vec<(u32, Vec<[f64; 3]>, EdgeInfo)> = new_graph
             .edges_directed(nx, EdgeDirection::Incoming)
             .map(|edge| edge.weight().clone())
             .collect();
-        let mut outgoing: Vec<(u32, Vec<[f64; 3]>)> = new_graph
+        let mut outgoing: Vec<(u32, Vec<[f64; 3]>, EdgeInfo)> = new_graph
             .edges_directed(nx, EdgeDirection::Outgoing)
             .map(|edge| edge.weight().clone())
             .collect();
         edges.append(&mut outgoing);
         let splits = get_temporal_splits(edges);
+        STATS.lock().unwrap().node_splits += splits.len();
         required_splits.push((nx, splits));
     }
     for (split_node, splits) in required_splits {
         // Split bbox at splits and add the new nodes
         let nodes: Vec<NodeIndex> = split_bbox(new_graph[split_node].1, &splits)
             .into_iter()
-            .map(|bbox| new_graph.add_node((new_graph[split_node].0, bbox)))
+            .map(|bbox| {
+                let (weight, _, info) = new_graph[split_node].clone();
+                new_graph.add_node((weight, bbox, info))
+            })
             .collect();
         reassign_edges(&mut new_graph, split_node, &nodes, EdgeDirection::Outgoing);
         reassign_edges(&mut new_graph, split_node, &nodes, EdgeDirection::Incoming);
@@ -237,7 +256,7 @@ fn split_bbox(bbox: Bbox, splits: &[f64]) -> Vec<Bbox> {
 }
 
 /// Determines where the cluster representative bbox should split
-fn get_temporal_splits(trjs: Vec<(u32, Vec<[f64; 3]>)>) -> Vec<f64> {
+fn get_temporal_splits(trjs: Vec<(u32, Vec<[f64; 3]>, EdgeInfo)>) -> Vec<f64> {
     let mut splits = vec![];
     let mut timestamps = vec![];
     for (idx, trj) in trjs.iter().map(|trj| trj.1.clone()).enumerate() {
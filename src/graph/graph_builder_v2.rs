@@ -1,92 +1,286 @@
-use std::collections::{HashMap, HashSet};
+use std::collections::HashSet;
 
 use itertools::Itertools;
 use petgraph::graph::NodeIndex;
+use rayon::prelude::*;
 
-use crate::arguments::EdgeRepresentative;
-use crate::graph::path_builder::split_stream_on_timeout;
+use crate::arguments::{EdgeRepresentative, Segmentation};
+use crate::graph::path_builder::{get_paths, split_stream_on_timeout};
+use crate::parser::{StreamId, TrajectoryId};
+use crate::utility::clustering::{self, ClusteringMethod};
+use crate::utility::cyclic_time::{self, MS_IN_24H};
+use crate::utility::grid_index::{BboxIndex, GridIndex};
 use crate::utility::trajectory::Trajectory;
-use crate::utility::{Bbox, Clustering};
-use crate::CLUSTERINGARGS;
+use crate::utility::Bbox;
+use crate::{CLUSTERINGARGS, CONFIG, STATS};
 
 use super::temporal_splitting::make_temporally_monotone;
-use super::{set_edges_centroid_trjs, set_edges_mediod_trjs, Graph, PathBuilderStats};
+use super::{
+    node_info::Visit, set_edges_centroid_trjs, set_edges_dba_trjs, set_edges_mediod_trjs, EdgeInfo,
+    Graph, NodeInfo, PathBuilderStats,
+};
 
 /// Constructs the graph
 /// Handles spatio-temporal clustering of paths + edge clustering.
-pub fn get_graph_v2(streams: Vec<Vec<[f64; 3]>>) -> Graph {
-    let mut graph: Graph = Graph::new();
+///
+/// Each trajectory connects the nodes of the clusters its endpoints belong to.
+pub fn get_graph_v2(streams: Vec<(StreamId, Vec<[f64; 3]>)>) -> Graph {
+    extend_graph(Graph::new(), streams)
+}
+
+/// Adds the trajectories of `streams` to an existing graph, e.g. one [read](super::read_graph) from `graph.json`.
+///
+/// Endpoints within the clustering threshold of an existing node are assigned to the nearest such node,
+/// whose bounding box grows to contain them. The remaining endpoints are clustered into new nodes.
+/// New edges are clustered together with the existing representatives, s.t. counts accumulate across runs.
+/// The [visits](super::node_info::Visit) of each stream are recorded in the nodes it passes through.
+pub fn extend_graph(mut graph: Graph, streams: Vec<(StreamId, Vec<[f64; 3]>)>) -> Graph {
     let mut path_stats = PathBuilderStats::default();
-    let trjs = streams
+    let (segmentation, vessels) = {
+        let args = CLUSTERINGARGS.lock().unwrap();
+        (args.segmentation, args.vessels)
+    };
+    // Trajectories are kept in the order of their streams, each identified by its stream and segment index
+    let (trjs, trj_ids): (Vec<Trajectory>, Vec<TrajectoryId>) = streams
         .into_iter()
-        .flat_map(|stream| split_stream_on_timeout(&stream, &mut path_stats))
-        .collect::<Vec<Trajectory>>();
+        .flat_map(|(id, stream)| {
+            segment_stream(stream, segmentation, &mut path_stats)
+                .into_iter()
+                .enumerate()
+                .map(move |(segment, trj)| {
+                    let stream = id.clone();
+                    (trj, TrajectoryId { stream, segment })
+                })
+        })
+        .unzip();
 
-    // Collect endpoints and store them in a tuple with their trj index
+    // The start of trajectory i is endpoint 2i and its end is endpoint 2i+1
     let endpoints: Vec<[f64; 3]> = trjs
         .iter()
         .flat_map(|trj| vec![trj[0], trj[trj.len() - 1]])
         .collect();
-    let clusters = cluster_endpoints(&endpoints);
-    let bboxs: Vec<Bbox> = clusters
-        .iter()
-        .map(|c| Bbox::new(&c.iter().map(|&idx| endpoints[idx]).collect_vec()))
-        .collect();
-    let mut cx_to_nx = HashMap::<usize, NodeIndex>::new();
-    for (cx, bbox) in bboxs.iter().enumerate() {
-        let cluster_size = clusters[cx].len() as u32;
-        let nx = graph.add_node((cluster_size, *bbox));
-        cx_to_nx.insert(cx, nx);
-    }
-    let mut nodes_to_split = vec![];
-    for trj in trjs {
-        let start = trj[0];
-        let end = trj[trj.len() - 1];
-        let mut start_nx: Option<NodeIndex> = None;
-        let mut end_nx: Option<NodeIndex> = None;
-        for nx in graph.node_indices() {
-            if graph[nx].1.contains_point(&start) {
-                start_nx = Some(nx);
-            }
-            if graph[nx].1.contains_point(&end) {
-                end_nx = Some(nx);
-            }
-            if start_nx.is_some() & end_nx.is_some() {
-                break;
-            }
+    let period = cyclic_time::period();
+    let mut endpoint_nx: Vec<Option<NodeIndex>> = assign_to_nodes(&graph, &endpoints, period);
+    for (idx, nx) in endpoint_nx.iter().enumerate() {
+        if let Some(nx) = nx {
+            let (weight, bbox, _) = &mut graph[*nx];
+            let [x, y, t] = endpoints[idx];
+            *weight += 1;
+            bbox.insert_point(&[x, y, cyclic_time::unwrap_into(t, bbox.t1, bbox.t2, period)]);
         }
-        if let (Some(start_nx), Some(end_nx)) = (start_nx, end_nx) {
-            if start_nx == end_nx {
-                nodes_to_split.push((start_nx, start, end));
-            }
-            graph.add_edge(start_nx, end_nx, (1, trj));
-        } else {
-            panic!("Endpoints of trajectory not contained in any node!");
+    }
+    let unassigned: Vec<usize> = (0..endpoints.len())
+        .filter(|idx| endpoint_nx[*idx].is_none())
+        .collect();
+    let clusters = cluster_endpoints(
+        &unassigned.iter().map(|idx| endpoints[*idx]).collect_vec(),
+        period,
+        CONFIG.endpoint_clustering,
+    );
+    STATS.lock().unwrap().spatial_clusters += clusters.len();
+    for cluster in clusters.iter() {
+        let members = cluster.iter().map(|i| unassigned[*i]).collect_vec();
+        let bbox = node_bbox(
+            &members.iter().map(|idx| endpoints[*idx]).collect_vec(),
+            period,
+        );
+        let nx = graph.add_node((members.len() as u32, bbox, NodeInfo::default()));
+        for idx in members {
+            endpoint_nx[idx] = Some(nx);
         }
     }
+    let endpoint_nx: Vec<NodeIndex> = endpoint_nx
+        .into_iter()
+        .map(|nx| nx.expect("Endpoints of trajectory not contained in any node!"))
+        .collect();
+    for (nx, visit) in get_visits(&trjs, &trj_ids, &endpoint_nx) {
+        graph[nx].2.visits.push(visit);
+    }
+    // Transport modes and map matching are inferred in parallel
+    let infos: Vec<EdgeInfo> = trjs
+        .par_iter()
+        .map(|trj| EdgeInfo::new(trj, vessels))
+        .collect();
+    for (i, ((trj, mut info), id)) in trjs.into_iter().zip(infos).zip(trj_ids).enumerate() {
+        info.members.push(id);
+        // Round trips start and end in the same node, which is split between departure and arrival
+        // when the graph is made temporally monotone
+        graph.add_edge(endpoint_nx[2 * i], endpoint_nx[2 * i + 1], (1, trj, info));
+    }
     make_temporally_monotone(&mut graph);
-    match CLUSTERINGARGS.lock().unwrap().cluster_strategy {
+    let cluster_strategy = CLUSTERINGARGS.lock().unwrap().cluster_strategy;
+    match cluster_strategy {
         EdgeRepresentative::Medoid => set_edges_mediod_trjs(&mut graph),
         EdgeRepresentative::Centroid => set_edges_centroid_trjs(&mut graph),
+        EdgeRepresentative::Dba => set_edges_dba_trjs(&mut graph),
     }
 
     graph
 }
 
-const MS_IN_24H: f64 = 24.0 * 60.0 * 60.0 * 1000.0;
+/// Returns the visits of the streams to the nodes of the endpoints of their trajectories.
+///
+/// An object arriving in a node and departing from the same node with its next trajectory visits it once.
+/// Otherwise it arrives without departing, or departs without arriving, e.g. at the start and end of the stream.
+fn get_visits(
+    trjs: &[Trajectory],
+    trj_ids: &[TrajectoryId],
+    endpoint_nx: &[NodeIndex],
+) -> Vec<(NodeIndex, Visit)> {
+    let mut visits = vec![];
+    let same_stream = |i: usize, j: usize| trj_ids[i].stream == trj_ids[j].stream;
+    for (i, trj) in trjs.iter().enumerate() {
+        let stream = &trj_ids[i].stream;
+        let (start_nx, end_nx) = (endpoint_nx[2 * i], endpoint_nx[2 * i + 1]);
+        let arrived = i > 0 && same_stream(i - 1, i) && endpoint_nx[2 * i - 1] == start_nx;
+        if !arrived {
+            let visit = Visit {
+                stream: stream.clone(),
+                arrival: None,
+                departure: Some(trj[0][2]),
+                arriving: None,
+                departing: Some(trj_ids[i].segment),
+                dwell: None,
+            };
+            visits.push((start_nx, visit));
+        }
+        let departs =
+            i + 1 < trjs.len() && same_stream(i, i + 1) && endpoint_nx[2 * i + 2] == end_nx;
+        let visit = Visit {
+            stream: stream.clone(),
+            arrival: Some(trj[trj.len() - 1][2]),
+            departure: if departs {
+                Some(trjs[i + 1][0][2])
+            } else {
+                None
+            },
+            arriving: Some(trj_ids[i].segment),
+            departing: if departs {
+                Some(trj_ids[i + 1].segment)
+            } else {
+                None
+            },
+            dwell: None,
+        };
+        visits.push((end_nx, visit));
+    }
+    visits
+}
+
+/// Returns the bounding box of the endpoints of a cluster.
+/// Its temporal extent is the shortest interval containing the endpoints when time wraps around after `period`.
+fn node_bbox(points: &[[f64; 3]], period: f64) -> Bbox {
+    let mut bbox = Bbox::new(points);
+    let ts = points.iter().map(|p| p[2]).collect_vec();
+    let (t1, t2) = cyclic_time::covering_interval(&ts, period);
+    bbox.t1 = t1;
+    bbox.t2 = t2;
+    bbox
+}
+
+/// Returns the existing node each endpoint fits in, if any.
+///
+/// An endpoint fits a node if its spatiotemporal distance to the bounding box of the node
+/// is below the clustering threshold, or if the box contains it.
+/// Candidate nodes are found using a [BboxIndex] over the boxes expanded by the threshold.
+/// Since boxes may wrap around the end of the period, the endpoint is looked up one period earlier and later as well.
+fn assign_to_nodes(graph: &Graph, endpoints: &[[f64; 3]], period: f64) -> Vec<Option<NodeIndex>> {
+    let temporal_slack: f64;
+    let threshold: f64;
+    {
+        let args = CLUSTERINGARGS.lock().unwrap();
+        temporal_slack = args.temporal_slack;
+        threshold = args.threshold;
+    }
+    let nodes: Vec<NodeIndex> = graph.node_indices().collect();
+    if nodes.is_empty() {
+        return vec![None; endpoints.len()];
+    }
+    // The temporal margin is infinite when time is ignored altogether
+    let time_scale = to_spatiotemporal(&[0.0, 0.0, 1.0], temporal_slack)[2];
+    let temporal_margin = if time_scale > 0.0 {
+        threshold / time_scale
+    } else {
+        f64::INFINITY
+    };
+    let expanded: Vec<Bbox> = nodes
+        .iter()
+        .map(|nx| {
+            let bbox = graph[*nx].1;
+            Bbox {
+                x1: bbox.x1 - threshold,
+                x2: bbox.x2 + threshold,
+                y1: bbox.y1 - threshold,
+                y2: bbox.y2 + threshold,
+                t1: bbox.t1 - temporal_margin,
+                t2: bbox.t2 + temporal_margin,
+            }
+        })
+        .collect();
+    let index = BboxIndex::new(expanded, NODE_INDEX_CELL_METERS);
+    endpoints
+        .par_iter()
+        .map(|p| {
+            [-period, 0.0, period]
+                .iter()
+                .flat_map(|shift| index.containing(&[p[0], p[1], p[2] + shift]))
+                .unique()
+                .map(|idx| {
+                    let bbox = &graph[nodes[idx]].1;
+                    (idx, distance_to_bbox(p, bbox, temporal_slack, period))
+                })
+                .filter(|(_, dist)| (*dist == 0.0) | (*dist < threshold))
+                .min_by(|(_, a), (_, b)| a.partial_cmp(b).unwrap())
+                .map(|(idx, _)| nodes[idx])
+        })
+        .collect()
+}
+
+/// Cuts the stream into trajectories according to `segmentation`.
+///
+/// When segmenting at stops, the routes between consecutive stops of each [path](super::Path)
+/// become trajectories, i.e. their endpoints lie in the stops.
+fn segment_stream(
+    stream: Vec<[f64; 3]>,
+    segmentation: Segmentation,
+    path_stats: &mut PathBuilderStats,
+) -> Vec<Trajectory> {
+    match segmentation {
+        Segmentation::Timeout => split_stream_on_timeout(&stream, path_stats),
+        Segmentation::Stops => get_paths(stream, path_stats)
+            .into_iter()
+            .flat_map(|path| path.get_trjs())
+            .filter(|trj| trj.len() > 1)
+            .collect(),
+    }
+}
 
-/// Returns spatiotemporal distance
-/// The distance is a weighted Euclidean distance based on `temporal_slack` which ranges from zero to `T`
+/// Cell size of the spatial index used to locate the existing nodes near an endpoint.
+const NODE_INDEX_CELL_METERS: f64 = 250.0;
+
+/// Maps `p` to the space where Euclidean distance equals the spatiotemporal distance.
+/// The spatiotemporal distance is a weighted Euclidean distance based on `temporal_slack` which ranges from zero to `T`
 /// where `T` is the number of ms in a 24 hour period.
-/// The Euclidean distance between the 3D points `p` and `q` has its temporal
+/// The Euclidean distance between the 3D points has its temporal
 /// dimension weighted by `T`-`temporal_slack`.
-fn spatiotemporal_distance(q: &[f64; 3], p: &[f64; 3], temporal_slack: f64) -> f64 {
-    let weight = (MS_IN_24H - temporal_slack) / 1000.0;
-    ((p[0] - q[0]).powi(2) + (p[1] - q[1]).powi(2) + weight * (p[2] - q[2]).powi(2)).sqrt()
+fn to_spatiotemporal(p: &[f64; 3], temporal_slack: f64) -> [f64; 3] {
+    let weight = ((MS_IN_24H - temporal_slack) / 1000.0).max(0.0);
+    [p[0], p[1], p[2] * weight.sqrt()]
 }
 
-/// Returns an agglomerative clustering of the endpoints
-fn cluster_endpoints(endpoints: &[[f64; 3]]) -> Vec<HashSet<usize>> {
+/// Returns a clustering of the endpoints using `method`, which is the `endpoint_clustering` of the config.
+///
+/// Single-linkage with a distance threshold yields the connected components of the graph
+/// where endpoints closer than `threshold` are adjacent, and DBSCAN only needs to know these neighbours.
+/// Both are found using a [GridIndex] over the spatiotemporal space, s.t. neither a distance matrix
+/// nor the pairs of close endpoints are ever stored.
+/// The remaining methods require the distance matrix of each connected component.
+/// The temporal dimension wraps around after `period`, s.t. endpoints on either side of midnight may be clustered.
+fn cluster_endpoints(
+    endpoints: &[[f64; 3]],
+    period: f64,
+    method: ClusteringMethod,
+) -> Vec<HashSet<usize>> {
     let temporal_slack: f64;
     let threshold: f64;
     {
@@ -94,14 +288,237 @@ fn cluster_endpoints(endpoints: &[[f64; 3]]) -> Vec<HashSet<usize>> {
         temporal_slack = args.temporal_slack;
         threshold = args.threshold;
     }
-    let n = endpoints.len();
-    let mut dists = vec![vec![0.0; n]; n];
-    for i in 0..n {
-        for j in i + 1..n {
-            dists[i][j] = spatiotemporal_distance(&endpoints[i], &endpoints[j], temporal_slack);
-            dists[j][i] = dists[i][j];
+    let points: Vec<[f64; 3]> = endpoints
+        .iter()
+        .map(|p| to_spatiotemporal(p, temporal_slack))
+        .collect();
+    // Time is ignored altogether if the scaled period vanishes
+    let period = to_spatiotemporal(&[0.0, 0.0, period], temporal_slack)[2];
+    let index = |radius: f64| {
+        if period > 0.0 {
+            GridIndex::periodic(points.clone(), radius, period)
+        } else {
+            GridIndex::new(points.clone(), radius)
         }
+    };
+    match method {
+        ClusteringMethod::Single if threshold > 0.0 => index(threshold).components().sets(),
+        ClusteringMethod::Dbscan if threshold > 0.0 => {
+            let index = index(threshold);
+            let neighbours =
+                |i: usize, visit: &mut dyn FnMut(usize)| index.for_each_neighbour(i, visit);
+            clustering::dbscan(endpoints.len(), neighbours, CONFIG.dbscan_min_points)
+        }
+        // Nothing is close without a threshold
+        ClusteringMethod::Single | ClusteringMethod::Dbscan => {
+            (0..endpoints.len()).map(|i| HashSet::from([i])).collect()
+        }
+        method => {
+            // Endpoints in separate components of the threshold graph are never closer than `threshold`,
+            // so each component is clustered on its own. This is exact for complete and average linkage,
+            // while Ward linkage and HDBSCAN are approximated.
+            let components = if threshold > 0.0 {
+                index(threshold).components().sets()
+            } else {
+                vec![(0..endpoints.len()).collect()]
+            };
+            let mut clusters: Vec<HashSet<usize>> = components
+                .into_par_iter()
+                .flat_map_iter(|component| {
+                    let members = component.into_iter().sorted().collect_vec();
+                    let dists = members
+                        .iter()
+                        .map(|i| {
+                            let p = points[*i];
+                            members
+                                .iter()
+                                .map(|j| {
+                                    let mut q = points[*j];
+                                    if period > 0.0 {
+                                        q[2] = cyclic_time::unwrap_into(q[2], p[2], p[2], period);
+                                    }
+                                    distance(&p, &q)
+                                })
+                                .collect()
+                        })
+                        .collect();
+                    clustering::cluster(dists, threshold, method)
+                        .into_iter()
+                        .map(|cluster| cluster.into_iter().map(|k| members[k]).collect())
+                        .collect_vec()
+                })
+                .collect();
+            clusters.sort_by_key(|cluster| *cluster.iter().min().unwrap());
+            clusters
+        }
+    }
+}
+
+fn distance(p: &[f64; 3], q: &[f64; 3]) -> f64 {
+    ((p[0] - q[0]).powi(2) + (p[1] - q[1]).powi(2) + (p[2] - q[2]).powi(2)).sqrt()
+}
+
+/// Spatiotemporal distance from `p` to the nearest point of `bbox`, with time wrapping around after `period`.
+fn distance_to_bbox(p: &[f64; 3], bbox: &Bbox, temporal_slack: f64, period: f64) -> f64 {
+    let gap = |v: f64, lo: f64, hi: f64| (lo - v).max(v - hi).max(0.0);
+    let t = cyclic_time::unwrap_into(p[2], bbox.t1, bbox.t2, period);
+    let gaps = [
+        gap(p[0], bbox.x1, bbox.x2),
+        gap(p[1], bbox.y1, bbox.y2),
+        gap(t, bbox.t1, bbox.t2),
+    ];
+    distance(&to_spatiotemporal(&gaps, temporal_slack), &[0.0; 3])
+}
+
+#[cfg(test)]
+mod test {
+    use super::*;
+
+    #[test]
+    fn visits_join_arrivals_and_departures_of_a_stream() {
+        let (home, work) = (NodeIndex::new(0), NodeIndex::new(1));
+        let id = |day: &str| StreamId {
+            day: day.to_string(),
+            ..Default::default()
+        };
+        let trj_id = |day: &str, segment| TrajectoryId {
+            stream: id(day),
+            segment,
+        };
+        let trj_ids = vec![
+            trj_id("2023-01-02", 0),
+            trj_id("2023-01-02", 1),
+            trj_id("2023-01-03", 0),
+        ];
+        let trj = |t1: f64, t2: f64| vec![[0.0, 0.0, t1], [0.0, 0.0, t2]];
+        // Home to work and back on the first day, but only to work on the second
+        let trjs = vec![trj(1.0, 2.0), trj(5.0, 6.0), trj(1.0, 3.0)];
+        let endpoint_nx = vec![home, work, work, home, home, work];
+        let visits = get_visits(&trjs, &trj_ids, &endpoint_nx);
+        let visit = |day: &str, arrival, departure, arriving, departing| Visit {
+            stream: id(day),
+            arrival,
+            departure,
+            arriving,
+            departing,
+            dwell: None,
+        };
+        assert_eq!(
+            visits,
+            vec![
+                (home, visit("2023-01-02", None, Some(1.0), None, Some(0))),
+                (
+                    work,
+                    visit("2023-01-02", Some(2.0), Some(5.0), Some(0), Some(1))
+                ),
+                (home, visit("2023-01-02", Some(6.0), None, Some(1), None)),
+                (home, visit("2023-01-03", None, Some(1.0), None, Some(0))),
+                (work, visit("2023-01-03", Some(3.0), None, Some(0), None)),
+            ]
+        );
+        // The trajectories of the first day meet at work
+        let info = NodeInfo {
+            visits: visits
+                .into_iter()
+                .filter(|(nx, _)| *nx == work)
+                .map(|(_, visit)| visit)
+                .collect(),
+            ..NodeInfo::default()
+        };
+        assert_eq!(
+            info.members(),
+            vec![
+                trj_id("2023-01-02", 0),
+                trj_id("2023-01-02", 1),
+                trj_id("2023-01-03", 0),
+            ]
+        );
+    }
+
+    /// Returns a stream staying at home for an hour, moving 3km to work in 30 minutes, staying there for an hour
+    /// and returning home in 30 minutes. Points are sampled every minute.
+    fn commute() -> Vec<[f64; 3]> {
+        let minute = 60.0 * 1000.0;
+        let home = (0..60).map(|i| [(i % 5) as f64, 0.0, i as f64 * minute]);
+        let there = (0..30).map(|i| [i as f64 * 100.0, 0.0, (60 + i) as f64 * minute]);
+        let work = (0..60).map(|i| [3000.0 + (i % 5) as f64, 0.0, (90 + i) as f64 * minute]);
+        let back = (0..30).map(|i| [3000.0 - i as f64 * 100.0, 0.0, (150 + i) as f64 * minute]);
+        home.chain(there).chain(work).chain(back).collect()
+    }
+
+    #[test]
+    fn segment_stream_at_stops_between_stays() {
+        let stream = commute();
+        let mut stats = PathBuilderStats::default();
+        let trjs = segment_stream(stream, Segmentation::Stops, &mut stats);
+        // The stay at work separates the route there from the route back
+        assert_eq!(trjs.len(), 2);
+        assert!(trjs[0][0][0] < 100.0);
+        assert!(trjs[1][0][0] > 2900.0);
+        // Both meet in the same point of the stay
+        assert_eq!(trjs[0][trjs[0].len() - 1], trjs[1][0]);
+    }
+
+    #[test]
+    fn segment_stream_on_timeout_only_at_gaps() {
+        let mut stream = commute();
+        let mut stats = PathBuilderStats::default();
+        let trjs = segment_stream(stream.clone(), Segmentation::Timeout, &mut stats);
+        assert_eq!(trjs, vec![stream.clone()]);
+        // A gap of an hour before work
+        for p in stream[90..].iter_mut() {
+            p[2] += 60.0 * 60.0 * 1000.0;
+        }
+        let trjs = segment_stream(stream.clone(), Segmentation::Timeout, &mut stats);
+        assert_eq!(trjs, vec![stream[..90].to_vec(), stream[90..].to_vec()]);
+    }
+
+    #[test]
+    fn cluster_endpoints_by_single_linkage() {
+        // A chain of endpoints 30m apart, a distant endpoint, and one at the start of the chain 12 hours later
+        let endpoints = vec![
+            [0.0, 0.0, 0.0],
+            [30.0, 0.0, 0.0],
+            [60.0, 0.0, 0.0],
+            [1000.0, 0.0, 0.0],
+            [0.0, 0.0, MS_IN_24H / 2.0],
+        ];
+        let mut clusters = cluster_endpoints(&endpoints, MS_IN_24H, ClusteringMethod::Single);
+        clusters.sort_by_key(|c| *c.iter().min().unwrap());
+        assert_eq!(clusters.len(), 3);
+        assert_eq!(clusters[0], HashSet::from([0, 1, 2]));
+    }
+
+    #[test]
+    fn cluster_endpoints_by_complete_linkage_within_components() {
+        // The chain is a single component, which complete linkage splits as its ends are 65m apart
+        let endpoints = vec![
+            [0.0, 0.0, 0.0],
+            [30.0, 0.0, 0.0],
+            [65.0, 0.0, 0.0],
+            [1000.0, 0.0, 0.0],
+            [1010.0, 0.0, 0.0],
+        ];
+        let clusters = cluster_endpoints(&endpoints, MS_IN_24H, ClusteringMethod::Complete);
+        assert_eq!(clusters.len(), 3);
+        assert_eq!(clusters[0], HashSet::from([0, 1]));
+        assert_eq!(clusters[1], HashSet::from([2]));
+        assert_eq!(clusters[2], HashSet::from([3, 4]));
+    }
+
+    #[test]
+    fn cluster_endpoints_around_midnight() {
+        // The default temporal slack only tolerates fractions of a ms between endpoints
+        let endpoints = vec![
+            [0.0, 0.0, MS_IN_24H - 0.05],
+            [0.0, 0.0, 0.05],
+            [0.0, 0.0, 0.5],
+        ];
+        let clusters = cluster_endpoints(&endpoints, MS_IN_24H, ClusteringMethod::Single);
+        assert_eq!(clusters.len(), 2);
+        assert_eq!(clusters[0], HashSet::from([0, 1]));
+        let bbox = node_bbox(&[endpoints[0], endpoints[1]], MS_IN_24H);
+        assert_eq!(bbox.t1, endpoints[0][2]);
+        assert!((bbox.t2 - (MS_IN_24H + 0.05)).abs() < 1e-6);
     }
-    let clustering = Clustering::new(dists, threshold);
-    clustering.clusters
 }
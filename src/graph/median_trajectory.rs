@@ -4,6 +4,8 @@ use super::Graph;
 use petgraph::graph::EdgeIndex;
 use trajectory_similarity::dissim::similarity;
 
+/// Returns the edge minimizing the sum of distances to the other edges.
+/// Distances are weighted by edge counts, s.t. a representative of many trajectories weighs as much as its members.
 pub fn get_mediod_trj(graph: &Graph, edges: &[EdgeIndex]) -> EdgeIndex {
     let mut dists = vec![vec![0.0; edges.len()]; edges.len()];
     for i in 0..edges.len() {
@@ -17,9 +19,13 @@ pub fn get_mediod_trj(graph: &Graph, edges: &[EdgeIndex]) -> EdgeIndex {
             dists[j][i] = dists[i][j];
         }
     }
+    let counts: Vec<f64> = edges
+        .iter()
+        .map(|ex| graph.edge_weight(*ex).unwrap().0 as f64)
+        .collect();
     let idx_min = dists
         .iter()
-        .map(|d| d.iter().sum::<f64>())
+        .map(|d| d.iter().zip(counts.iter()).map(|(d, w)| d * w).sum::<f64>())
         .enumerate()
         .min_by(|(_, a), (_, b)| a.partial_cmp(b).unwrap_or(std::cmp::Ordering::Equal))
         .map(|(index, _)| index)
@@ -1,20 +1,179 @@
+mod day_type;
 mod edge_clustering;
+mod edge_info;
 mod graph;
 mod graph_builder;
 mod graph_builder_v2;
 mod median_trajectory;
 mod node_clustering;
+mod node_info;
 mod path;
 mod path_builder;
 mod path_builder_stats;
 mod path_element;
+mod segmentation;
+mod spread;
+mod stay;
+mod support;
 mod temporal_splitting;
-pub use edge_clustering::{set_edges_centroid_trjs, set_edges_mediod_trjs};
-pub use graph::{DetourGraph, Graph, Writable};
+mod travel_times;
+mod validation;
+pub use day_type::{classify_days, group_by_day_type, read_holidays};
+pub use edge_clustering::{set_edges_centroid_trjs, set_edges_dba_trjs, set_edges_mediod_trjs};
+pub use edge_info::{write_assignments, EdgeInfo};
+pub use graph::{read_graph, DetourGraph, Graph, Writable};
 pub use graph_builder::get_graph;
-pub use graph_builder_v2::get_graph_v2;
+pub use graph_builder_v2::{extend_graph, get_graph_v2};
 pub use median_trajectory::get_mediod_trj;
 pub use node_clustering::spatially_cluster_nodes;
+pub use node_info::{write_node_visits, write_visits, NodeInfo, Visit};
 pub use path::Path;
 pub use path_builder_stats::PathBuilderStats;
 pub use path_element::PathElement;
+pub use segmentation::write_segmentation;
+pub use spread::{write_corridors, Spread};
+pub use stay::{get_stays, write_stays};
+pub use support::Support;
+pub use travel_times::{write_edge_times, Distribution, TravelTimes};
+pub use validation::validate;
+
+use crate::arguments::GraphBuilder;
+use crate::parser::StreamId;
+
+/// Constructs the graph from the daily streams using the selected builder.
+pub fn build_graph(
+    streams: Vec<(StreamId, Vec<[f64; 3]>)>,
+    builder: GraphBuilder,
+) -> Box<dyn Writable> {
+    match builder {
+        GraphBuilder::V1 => Box::new(get_graph(streams)),
+        GraphBuilder::V2 => Box::new(get_graph_v2(streams)),
+    }
+}
+
+#[cfg(test)]
+mod test {
+    use super::*;
+    use petgraph::algo::is_cyclic_directed;
+
+    const MINUTE: f64 = 60.0 * 1000.0;
+
+    /// A day of commuting between home at (0,0) and work 3km east of it.
+    /// Points are sampled every minute with a few meters of noise.
+    fn commute(day: usize) -> Vec<[f64; 3]> {
+        let offset = (day % 3) as f64 * 5.0 * MINUTE;
+        let mut stream = vec![];
+        let mut t = 7.0 * 60.0 * MINUTE + offset;
+        let stay = |stream: &mut Vec<[f64; 3]>, t: &mut f64, x: f64, minutes: usize| {
+            for i in 0..minutes {
+                let noise = (i % 5) as f64;
+                stream.push([x + noise, noise, *t]);
+                *t += MINUTE;
+            }
+        };
+        stay(&mut stream, &mut t, 0.0, 60);
+        for i in 0..30 {
+            stream.push([i as f64 * 100.0, 0.0, t]);
+            t += MINUTE;
+        }
+        stay(&mut stream, &mut t, 3000.0, 8 * 60);
+        for i in 0..30 {
+            stream.push([3000.0 - i as f64 * 100.0, 0.0, t]);
+            t += MINUTE;
+        }
+        stay(&mut stream, &mut t, 0.0, 60);
+        stream
+    }
+
+    fn streams() -> Vec<(StreamId, Vec<[f64; 3]>)> {
+        (0..3)
+            .map(|day| {
+                let id = StreamId {
+                    day: format!("2023-01-0{}", day + 2),
+                    ..Default::default()
+                };
+                (id, commute(day))
+            })
+            .collect()
+    }
+
+    #[test]
+    fn builders_construct_acyclic_graphs() {
+        for builder in [GraphBuilder::V1, GraphBuilder::V2] {
+            let graph = build_graph(streams(), builder);
+            let graph = graph.as_graph();
+            assert!(graph.node_count() > 0);
+            assert!(graph.edge_count() > 0);
+            assert!(!is_cyclic_directed(graph));
+        }
+    }
+
+    #[test]
+    fn extending_with_the_same_days_accumulates_counts() {
+        let graph = get_graph_v2(streams());
+        let count = |graph: &Graph| graph.edge_weights().map(|w| w.0).sum::<u32>();
+        let nodes = graph.node_count();
+        let edges = graph.edge_count();
+        let trjs = count(&graph);
+        let graph = extend_graph(graph, streams());
+        assert_eq!(graph.node_count(), nodes);
+        assert_eq!(graph.edge_count(), edges);
+        assert_eq!(count(&graph), 2 * trjs);
+        assert!(!is_cyclic_directed(&graph));
+    }
+
+    #[test]
+    fn builders_construct_valid_graphs() {
+        for builder in [GraphBuilder::V1, GraphBuilder::V2] {
+            let graph = build_graph(streams(), builder);
+            assert_eq!(validate(graph.as_graph()), vec![]);
+        }
+        let graph = extend_graph(get_graph_v2(streams()), streams());
+        assert_eq!(validate(&graph), vec![]);
+    }
+
+    #[test]
+    fn written_graph_is_read_back_valid() {
+        let graph = get_graph_v2(streams());
+        let folder = std::env::temp_dir().join(format!("detour-{}", std::process::id()));
+        std::fs::create_dir_all(&folder).unwrap();
+        let path = folder.join("graph.json");
+        std::fs::write(&path, serde_json::to_string(&graph).unwrap()).unwrap();
+        let read = read_graph(&path).unwrap();
+        std::fs::remove_dir_all(&folder).unwrap();
+        assert_eq!(read.node_count(), graph.node_count());
+        assert_eq!(read.edge_count(), graph.edge_count());
+        assert_eq!(validate(&read), vec![]);
+    }
+
+    #[test]
+    fn graph_does_not_depend_on_the_number_of_threads() {
+        for builder in [GraphBuilder::V1, GraphBuilder::V2] {
+            let build = |threads: usize| {
+                let pool = rayon::ThreadPoolBuilder::new()
+                    .num_threads(threads)
+                    .build()
+                    .unwrap();
+                pool.install(|| {
+                    let graph = build_graph(streams(), builder);
+                    serde_json::to_string(graph.as_graph()).unwrap()
+                })
+            };
+            assert_eq!(build(1), build(4));
+        }
+    }
+
+    #[test]
+    fn builders_keep_edge_endpoints_in_nodes() {
+        for builder in [GraphBuilder::V1, GraphBuilder::V2] {
+            let graph = build_graph(streams(), builder);
+            let graph = graph.as_graph();
+            for ex in graph.edge_indices() {
+                let (source, target) = graph.edge_endpoints(ex).unwrap();
+                let trj = &graph[ex].1;
+                assert!(graph[source].1.is_in_spatial(&trj[0]));
+                assert!(graph[target].1.is_in_spatial(&trj[trj.len() - 1]));
+            }
+        }
+    }
+}
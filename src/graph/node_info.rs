@@ -0,0 +1,221 @@
+use itertools::Itertools;
+use serde::{Deserialize, Serialize};
+use std::fs::File;
+use std::io::{BufWriter, Result, Write};
+use std::path::Path;
+
+use super::{Distribution, Graph, Support};
+use crate::parser::{StreamId, TrajectoryId};
+use crate::utility::csv_field;
+use crate::CONFIG;
+
+/// Information about the place a node represents.
+#[derive(Clone, Debug, Default, PartialEq, Serialize, Deserialize)]
+#[serde(default)]
+pub struct NodeInfo {
+    /// Identifies the place the node was temporally split from.
+    /// Nodes split from the same place share it. None until the graph is made temporally monotone.
+    pub place: Option<usize>,
+    /// The visits of the objects to the node.
+    pub visits: Vec<Visit>,
+}
+
+/// A visit to a node by the object of a stream.
+///
+/// A stream that starts in the node only departs from it, while a stream that ends in it only arrives.
+/// So does a visit split where the node is temporally split between its arrival and departure,
+/// but both halves keep the dwell time of the whole visit.
+#[derive(Clone, Debug, PartialEq, Serialize, Deserialize)]
+pub struct Visit {
+    pub stream: StreamId,
+    pub arrival: Option<f64>,
+    pub departure: Option<f64>,
+    /// Segment of the stream the object arrived with.
+    #[serde(default)]
+    pub arriving: Option<usize>,
+    /// Segment of the stream the object departed with.
+    #[serde(default)]
+    pub departing: Option<usize>,
+    /// Time the object spent in the place, if the visit was split between slices of it.
+    #[serde(default)]
+    pub dwell: Option<f64>,
+}
+
+impl Visit {
+    /// Returns the time the object spent in the node, if it both arrived and departed.
+    /// For a split visit, this is the time spent in the place the node was split from.
+    pub fn dwell_time(&self) -> Option<f64> {
+        self.dwell.or_else(|| Some(self.departure? - self.arrival?))
+    }
+}
+
+impl NodeInfo {
+    /// Returns the dwell time of every visit with both an arrival and a departure.
+    pub fn dwell_times(&self) -> Vec<f64> {
+        self.visits.iter().filter_map(Visit::dwell_time).collect()
+    }
+
+    /// Returns how many distinct objects and days visit the node.
+    pub fn support(&self) -> Support {
+        Support::new(self.visits.iter().map(|visit| &visit.stream))
+    }
+
+    /// Returns the input trajectories arriving in or departing from the node.
+    pub fn members(&self) -> Vec<TrajectoryId> {
+        self.visits
+            .iter()
+            .flat_map(|visit| {
+                vec![visit.arriving, visit.departing]
+                    .into_iter()
+                    .flatten()
+                    .map(move |segment| TrajectoryId {
+                        stream: visit.stream.clone(),
+                        segment,
+                    })
+            })
+            .collect()
+    }
+}
+
+/// Summarizes the visits of each node and the number of trajectories arriving or departing in `node_visits.csv`.
+///
+/// The share of days is relative to all days visiting the graph,
+/// and dwell time histogram counts are separated by spaces.
+pub fn write_node_visits(graph: &Graph, folder: &Path) -> Result<()> {
+    let days = Support::new(
+        graph
+            .node_weights()
+            .flat_map(|(_, _, info)| info.visits.iter().map(|visit| &visit.stream)),
+    )
+    .days;
+    let dwell_bin = CONFIG.dwell_bin_minutes * 60.0 * 1000.0;
+    let summaries = graph
+        .node_indices()
+        .map(|nx| {
+            let info = &graph[nx].2;
+            let dwell = Distribution::new(&info.dwell_times(), dwell_bin)
+                .map(|d| {
+                    format!(
+                        "{},{},{},{},{},{},{},{}",
+                        d.min,
+                        d.p10,
+                        d.p25,
+                        d.median,
+                        d.p75,
+                        d.p90,
+                        d.max,
+                        d.histogram.iter().join(" ")
+                    )
+                })
+                .unwrap_or_else(|| ",,,,,,,".to_string());
+            format!(
+                "{},{},{},{},{},{}",
+                nx.index(),
+                info.visits.len(),
+                info.members().len(),
+                info.support().days,
+                info.support().days as f64 / days.max(1) as f64,
+                dwell
+            )
+        })
+        .join("\n");
+    let f = File::create(folder.join("node_visits.csv"))?;
+    let mut f = BufWriter::new(f);
+    writeln!(
+        f,
+        "label,visits,trajectories,days,share_of_days,dwell_min,dwell_p10,dwell_p25,dwell_median,dwell_p75,dwell_p90,dwell_max,dwell_histogram\n{}",
+        summaries
+    )?;
+    Ok(())
+}
+
+/// Writes every visit with its dwell time and the segments of the stream arriving and departing to `visits.csv`.
+///
+/// Arrival or departure is empty if the stream started or ended in the node, or if the visit was split.
+pub fn write_visits(graph: &Graph, folder: &Path) -> Result<()> {
+    let visits = graph
+        .node_indices()
+        .flat_map(|nx| {
+            graph[nx].2.visits.iter().map(move |visit| {
+                let time = |t: Option<f64>| t.map(|t| t.to_string()).unwrap_or_default();
+                let segment = |s: Option<usize>| s.map(|s| s.to_string()).unwrap_or_default();
+                format!(
+                    "{},{},{},{},{},{},{},{},{}",
+                    nx.index(),
+                    csv_field(&visit.stream.source),
+                    csv_field(&visit.stream.object),
+                    visit.stream.day,
+                    time(visit.arrival),
+                    time(visit.departure),
+                    time(visit.dwell_time()),
+                    segment(visit.arriving),
+                    segment(visit.departing)
+                )
+            })
+        })
+        .join("\n");
+    let f = File::create(folder.join("visits.csv"))?;
+    let mut f = BufWriter::new(f);
+    writeln!(
+        f,
+        "label,source,object,day,arrival,departure,dwell,arriving,departing\n{}",
+        visits
+    )?;
+    Ok(())
+}
+
+#[cfg(test)]
+mod test {
+    use super::*;
+    use crate::utility::Bbox;
+
+    const MINUTE: f64 = 60.0 * 1000.0;
+
+    #[test]
+    fn summarize_dwell_times_of_node_visits() {
+        let visit = |day: &str, arrival: Option<f64>, departure: Option<f64>, dwell| Visit {
+            stream: StreamId {
+                day: day.to_string(),
+                ..Default::default()
+            },
+            arrival: arrival.map(|t| t * MINUTE),
+            departure: departure.map(|t| t * MINUTE),
+            arriving: arrival.map(|_| 0),
+            departing: departure.map(|_| 1),
+            dwell,
+        };
+        // Stays of 10 and 40 minutes, one of them split, and a stream ending in the node
+        let info = NodeInfo {
+            place: Some(0),
+            visits: vec![
+                visit("2023-01-02", Some(0.0), Some(10.0), None),
+                visit("2023-01-03", None, Some(50.0), Some(40.0 * MINUTE)),
+                visit("2023-01-04", Some(20.0), None, None),
+            ],
+        };
+        assert_eq!(info.dwell_times(), vec![10.0 * MINUTE, 40.0 * MINUTE]);
+        let mut graph = Graph::new();
+        graph.add_node((3, Bbox::new(&[[0.0, 0.0, 0.0], [1.0, 1.0, 1.0]]), info));
+        let folder = std::env::temp_dir().join(format!("detour-visits-{}", std::process::id()));
+        std::fs::create_dir_all(&folder).unwrap();
+        write_node_visits(&graph, &folder).unwrap();
+        let csv = std::fs::read_to_string(folder.join("node_visits.csv")).unwrap();
+        std::fs::remove_dir_all(&folder).unwrap();
+        let lines: Vec<&str> = csv.lines().collect();
+        assert_eq!(lines.len(), 2);
+        // Dwell times fall in the first two bins of 30 minutes
+        assert_eq!(
+            lines[1],
+            format!(
+                "0,3,4,3,1,{},{},{},{},{},{},{},1 1",
+                10.0 * MINUTE,
+                10.0 * MINUTE,
+                10.0 * MINUTE,
+                10.0 * MINUTE,
+                40.0 * MINUTE,
+                40.0 * MINUTE,
+                40.0 * MINUTE
+            )
+        );
+    }
+}
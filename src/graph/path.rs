@@ -191,4 +191,20 @@ impl Path {
     pub fn get_trjs(&self) -> Vec<Trajectory> {
         self.path.iter().flat_map(|pe| pe.copy_trj()).collect_vec()
     }
+
+    /// Returns the index of the first element containing `point`.
+    ///
+    /// Stops contain the points within their `Bbox` and routes contain the points within their timespan.
+    pub fn find_element(&self, point: &[f64; 3]) -> Option<usize> {
+        self.path.iter().position(|elm| match elm {
+            PathElement::Stop(bbox) => bbox.contains_point(point),
+            PathElement::Route(trj) => {
+                !trj.is_empty() && (trj[0][2]..=trj[trj.len() - 1][2]).contains(&point[2])
+            }
+        })
+    }
+
+    pub fn get_stops(&self) -> Vec<Bbox> {
+        self.path.iter().flat_map(|pe| pe.copy_bbox()).collect_vec()
+    }
 }
@@ -16,7 +16,7 @@ pub fn get_paths(stream: Vec<[f64; 3]>, stats: &mut PathBuilderStats) -> Vec<Pat
     let paths: Vec<Path> = splitted_streams
         .into_iter()
         .map(|stream| {
-            let path = build_path(stream);
+            let (path, _) = build_path(stream);
             stats.path_lens.push(path.len());
             path
         })
@@ -24,14 +24,26 @@ pub fn get_paths(stream: Vec<[f64; 3]>, stats: &mut PathBuilderStats) -> Vec<Pat
     paths
 }
 
+/// Output of the detectors for a single point.
+#[derive(Clone, Copy, Debug)]
+pub struct Detection {
+    /// Average velocity in km/h calculated by the [motion detector](MotionDetector).
+    /// `None` until the detector has measured any movement, i.e. for the first point of a stream.
+    pub velocity: Option<f64>,
+    pub motion: IsStopped,
+    pub stop: IsStopped,
+}
+
 /// Classify stops and routes of a stream
 ///
 /// Should be called after `split_stream`.
-fn build_path(stream: Vec<[f64; 3]>) -> Path {
+/// Returns the path along with the output of the detectors for each point.
+pub fn build_path(stream: Vec<[f64; 3]>) -> (Path, Vec<Detection>) {
     // This function should be called after split_stream
     let mut sd = StopDetector::new();
     let mut md = MotionDetector::new();
     let mut builder: PathBuilder = PathBuilder::new();
+    let mut detections = vec![];
     stream.into_iter().for_each(|point| {
         let md_stop: IsStopped = md.is_stopped(&point);
         // The stop detector switches to `IsStopped::No` when its spatial
@@ -44,10 +56,15 @@ fn build_path(stream: Vec<[f64; 3]>) -> Path {
         }
         let sd_stop: IsStopped = sd.is_stopped(&point);
         builder.add_pt(point, sd_stop);
+        detections.push(Detection {
+            velocity: Some(md.get_avg_velocity()).filter(|v| v.is_finite()),
+            motion: md_stop,
+            stop: sd_stop,
+        });
     });
     let path = builder.get_path();
     path.verify();
-    path
+    (path, detections)
 }
 
 /// Splits the stream if more than `CONFIG.connection_timeout` ms has passed
@@ -55,7 +72,15 @@ pub fn split_stream_on_timeout(
     stream: &[[f64; 3]],
     stats: &mut PathBuilderStats,
 ) -> Vec<Vec<[f64; 3]>> {
-    let connection_timeout = CONFIG.connection_timeout;
+    split_stream_at(stream, CONFIG.connection_timeout, stats)
+}
+
+/// Splits the stream if more than `connection_timeout` ms has passed
+fn split_stream_at(
+    stream: &[[f64; 3]],
+    connection_timeout: f64,
+    stats: &mut PathBuilderStats,
+) -> Vec<Vec<[f64; 3]>> {
     let mut last_timestamp = stream[0][2];
     let mut result = vec![];
     let mut partial_result = vec![];
@@ -239,7 +264,7 @@ mod test {
         ];
         let connection_timeout = 3.0;
         let mut stats = PathBuilderStats::default();
-        let streams = split_stream_on_timeout(&stream, &mut stats);
+        let streams = split_stream_at(&stream, connection_timeout, &mut stats);
         assert_eq!(streams.len(), 2);
         assert_eq!(streams[0].len(), 4);
         assert_eq!(streams[1].len(), 6);
@@ -0,0 +1,137 @@
+use super::path_builder::{build_path, split_stream_on_timeout, Detection};
+use super::PathBuilderStats;
+use crate::parser::StreamId;
+use crate::utility::csv_field;
+use itertools::Itertools;
+use std::fs::File;
+use std::io::{BufWriter, Result, Write};
+
+/// A point of a stream along with the reasoning behind its segmentation.
+#[derive(Clone, Debug)]
+pub struct SegmentedPoint {
+    pub point: [f64; 3],
+    /// Index of the part of the stream after splitting on `connection_timeout`.
+    pub segment: usize,
+    pub detection: Detection,
+    /// Index of the [path element](super::PathElement) containing the point.
+    /// `None` if the point was discarded while building the path.
+    pub element: Option<usize>,
+}
+
+/// Returns every point of the stream labelled by the detectors and the path builder.
+pub fn get_segmentation(stream: &[[f64; 3]], stats: &mut PathBuilderStats) -> Vec<SegmentedPoint> {
+    split_stream_on_timeout(stream, stats)
+        .into_iter()
+        .enumerate()
+        .flat_map(|(segment, stream)| {
+            let (path, detections) = build_path(stream.clone());
+            stream
+                .into_iter()
+                .zip(detections)
+                .map(move |(point, detection)| SegmentedPoint {
+                    point,
+                    segment,
+                    detection,
+                    element: path.find_element(&point),
+                })
+                .collect_vec()
+        })
+        .collect()
+}
+
+/// Writes the segmentation of every stream to `segmentation.csv`.
+///
+/// Each row holds a point along with the average velocity and state of the `MotionDetector`,
+/// the state of the `StopDetector` and the index of the path element the point ended up in.
+/// The velocity is empty for the first point of each segment.
+pub fn write_segmentation(streams: &[(StreamId, Vec<[f64; 3]>)]) -> Result<()> {
+    let f = File::create("segmentation.csv")?;
+    write_segmentation_rows(&mut BufWriter::new(f), streams)
+}
+
+fn write_segmentation_rows(
+    f: &mut impl Write,
+    streams: &[(StreamId, Vec<[f64; 3]>)],
+) -> Result<()> {
+    let mut stats = PathBuilderStats::default();
+    writeln!(
+        f,
+        "stream,source,object,day,segment,x,y,t,velocity,motion,stop,element"
+    )?;
+    for (idx, (id, stream)) in streams.iter().enumerate() {
+        for pt in get_segmentation(stream, &mut stats) {
+            let velocity = pt
+                .detection
+                .velocity
+                .map(|v| v.to_string())
+                .unwrap_or_default();
+            let element = pt.element.map(|e| e.to_string()).unwrap_or_default();
+            writeln!(
+                f,
+                "{},{},{},{},{},{},{},{},{},{:?},{:?},{}",
+                idx,
+                csv_field(&id.source),
+                csv_field(&id.object),
+                id.day,
+                pt.segment,
+                pt.point[0],
+                pt.point[1],
+                pt.point[2],
+                velocity,
+                pt.detection.motion,
+                pt.detection.stop,
+                element
+            )?;
+        }
+    }
+    Ok(())
+}
+
+#[cfg(test)]
+mod test {
+    use super::*;
+
+    const MINUTE: f64 = 60.0 * 1000.0;
+
+    #[test]
+    fn label_motion_and_stop_of_each_point() {
+        // Half an hour moving 3km east, an hour at (3000,0), then half an hour moving further east
+        let mut stream = vec![];
+        for i in 0..30 {
+            stream.push([i as f64 * 100.0, 0.0, i as f64 * MINUTE]);
+        }
+        for i in 0..60 {
+            stream.push([3000.0 + (i % 3) as f64, 0.0, (30 + i) as f64 * MINUTE]);
+        }
+        for i in 0..30 {
+            stream.push([3000.0 + i as f64 * 100.0, 0.0, (90 + i) as f64 * MINUTE]);
+        }
+        let id = StreamId {
+            source: "a,b.csv".to_string(),
+            ..Default::default()
+        };
+        let mut f = vec![];
+        write_segmentation_rows(&mut f, &[(id, stream)]).unwrap();
+        let csv = String::from_utf8(f).unwrap();
+        let mut lines = csv.lines();
+        assert_eq!(
+            lines.next(),
+            Some("stream,source,object,day,segment,x,y,t,velocity,motion,stop,element")
+        );
+        let rows = lines.collect_vec();
+        assert_eq!(rows.len(), 120);
+        // Motion, stop and path element of a row
+        let labels = |row: &str| {
+            let mut fields = row.rsplit(',').take(3).collect_vec();
+            fields.reverse();
+            fields.join(",")
+        };
+        assert!(rows.iter().all(|row| row.starts_with("0,\"a,b.csv\",,,0,")));
+        // The velocity is undefined at the first point
+        assert!(rows[0].starts_with("0,\"a,b.csv\",,,0,0,0,0,,"));
+        assert!(!csv.contains("NaN"));
+        assert_eq!(labels(rows[20]), "No,No,1");
+        assert_eq!(labels(rows[60]), "Yes,Yes,2");
+        assert_eq!(labels(rows[110]), "No,No,3");
+    }
+}
@@ -0,0 +1,319 @@
+use itertools::Itertools;
+use petgraph::visit::{EdgeRef, IntoEdgeReferences};
+use serde::{Deserialize, Serialize};
+use serde_json::json;
+use std::fs::File;
+use std::io::{Result, Write};
+use std::path::Path;
+
+use super::Graph;
+use crate::{from_epsg_3857_to_4326, CONFIG};
+
+/// Largest number of weighted distances kept by a [Spread].
+const SUMMARY_SIZE: usize = 64;
+
+/// Largest factor by which the corridor is widened at the vertices of the representative.
+const MITER_LIMIT: f64 = 4.0;
+
+/// How far the trajectories of an edge deviate from its representative.
+///
+/// Distances are measured spatially from every point of the member trajectories to the representative,
+/// weighted by the number of trajectories each member represents.
+#[derive(Clone, Debug, Default, PartialEq, Serialize, Deserialize)]
+#[serde(default)]
+pub struct Spread {
+    /// Mean distance in meters.
+    pub mean_meters: f64,
+    /// Distance in meters within which the `corridor_percentile` of the points lie.
+    pub percentile_meters: f64,
+    /// Polygon around the representative covering the `corridor_percentile` of the points.
+    pub corridor: Vec<[f64; 2]>,
+    /// Weighted distances in meters sorted by distance, compressed into at most [SUMMARY_SIZE] entries.
+    /// Kept so the spread of the trajectories survives when the edge is clustered again, e.g. by `--extend`.
+    pub distances: Vec<[f64; 2]>,
+}
+
+impl Spread {
+    /// Returns the spread of the `members` of an edge around the `representative`.
+    /// Each member is given with its count and its own spread.
+    ///
+    /// A member whose spread records distances already represents several trajectories.
+    /// Its recorded distances are used instead of those of its own points,
+    /// shifted by the mean distance from its points to the `representative`.
+    pub fn new(representative: &[[f64; 3]], members: &[(u32, &[[f64; 3]], &Spread)]) -> Self {
+        let mut dists: Vec<[f64; 2]> = vec![];
+        for (count, trj, spread) in members {
+            let own: Vec<f64> = trj
+                .iter()
+                .map(|p| distance_to_polyline(p, representative))
+                .collect();
+            if spread.distances.is_empty() {
+                dists.extend(own.iter().map(|d| [*d, *count as f64]));
+            } else if !own.is_empty() {
+                let shift = own.iter().sum::<f64>() / own.len() as f64;
+                dists.extend(spread.distances.iter().map(|[d, w]| [d + shift, *w]));
+            }
+        }
+        let distances = summarise(dists);
+        if distances.is_empty() {
+            return Spread::default();
+        }
+        let total: f64 = distances.iter().map(|[_, w]| w).sum();
+        let mean_meters = distances.iter().map(|[d, w]| d * w).sum::<f64>() / total;
+        let mut covered = 0.0;
+        let mut percentile_meters = distances[distances.len() - 1][0];
+        for [d, w] in distances.iter() {
+            covered += w;
+            if covered >= CONFIG.corridor_percentile * total {
+                percentile_meters = *d;
+                break;
+            }
+        }
+        Spread {
+            mean_meters,
+            percentile_meters,
+            corridor: buffer(representative, percentile_meters),
+            distances,
+        }
+    }
+}
+
+/// Sorts the weighted distances and merges equal ones.
+/// If there are more than [SUMMARY_SIZE] left, they are merged into entries of similar weight,
+/// each at the weighted mean of the distances it covers, which keeps the mean distance.
+fn summarise(mut dists: Vec<[f64; 2]>) -> Vec<[f64; 2]> {
+    dists.sort_by(|a, b| a[0].partial_cmp(&b[0]).unwrap());
+    let mut merged: Vec<[f64; 2]> = vec![];
+    for [d, w] in dists {
+        match merged.last_mut() {
+            Some(last) if last[0] == d => last[1] += w,
+            _ => merged.push([d, w]),
+        }
+    }
+    if merged.len() <= SUMMARY_SIZE {
+        return merged;
+    }
+    let bin = merged.iter().map(|[_, w]| w).sum::<f64>() / SUMMARY_SIZE as f64;
+    let mut summary = vec![];
+    let (mut sum, mut weight) = (0.0, 0.0);
+    for [d, w] in merged {
+        sum += d * w;
+        weight += w;
+        if weight >= bin {
+            summary.push([sum / weight, weight]);
+            sum = 0.0;
+            weight = 0.0;
+        }
+    }
+    if weight > 0.0 {
+        summary.push([sum / weight, weight]);
+    }
+    summary
+}
+
+/// Returns the spatial distance from `p` to the closest point on the polyline.
+fn distance_to_polyline(p: &[f64; 3], line: &[[f64; 3]]) -> f64 {
+    if line.len() == 1 {
+        return ((p[0] - line[0][0]).powi(2) + (p[1] - line[0][1]).powi(2)).sqrt();
+    }
+    line.windows(2)
+        .map(|segment| {
+            let (a, b) = (segment[0], segment[1]);
+            let (dx, dy) = (b[0] - a[0], b[1] - a[1]);
+            let length = dx * dx + dy * dy;
+            let s = if length > 0.0 {
+                (((p[0] - a[0]) * dx + (p[1] - a[1]) * dy) / length).clamp(0.0, 1.0)
+            } else {
+                0.0
+            };
+            ((p[0] - a[0] - s * dx).powi(2) + (p[1] - a[1] - s * dy).powi(2)).sqrt()
+        })
+        .fold(f64::INFINITY, f64::min)
+}
+
+/// Returns a closed polygon around the polyline with the given radius.
+///
+/// The sides are offset along the bisecting normal at each vertex, scaled by 1/cos(θ/2) for a turn of θ
+/// so the sides stay `radius` away from both segments, up to [MITER_LIMIT] at sharp turns.
+/// Both ends are capped by half circles.
+fn buffer(line: &[[f64; 3]], radius: f64) -> Vec<[f64; 2]> {
+    let points: Vec<[f64; 2]> = line.iter().map(|[x, y, _]| [*x, *y]).collect();
+    let normal = |a: [f64; 2], b: [f64; 2]| {
+        let (dx, dy) = (b[0] - a[0], b[1] - a[1]);
+        let length = (dx * dx + dy * dy).sqrt();
+        if length > 0.0 {
+            [-dy / length, dx / length]
+        } else {
+            [0.0, 0.0]
+        }
+    };
+    let n = points.len();
+    // Normals at each vertex are the average of the normals of its segments, scaled to the miter length
+    let normals: Vec<[f64; 2]> = (0..n)
+        .map(|i| {
+            let before = if i > 0 {
+                normal(points[i - 1], points[i])
+            } else {
+                [0.0, 0.0]
+            };
+            let after = if i + 1 < n {
+                normal(points[i], points[i + 1])
+            } else {
+                [0.0, 0.0]
+            };
+            let [x, y] = [before[0] + after[0], before[1] + after[1]];
+            let length = (x * x + y * y).sqrt();
+            if length == 0.0 {
+                return [0.0, 1.0];
+            }
+            let [x, y] = [x / length, y / length];
+            // cos(θ/2) is the projection of the bisector onto the normal of either segment
+            let segment = if i + 1 < n { after } else { before };
+            let cos = x * segment[0] + y * segment[1];
+            let scale = if cos > 0.0 {
+                (1.0 / cos).min(MITER_LIMIT)
+            } else {
+                1.0
+            };
+            [x * scale, y * scale]
+        })
+        .collect();
+    let offset = |i: usize, side: f64| {
+        [
+            points[i][0] + side * radius * normals[i][0],
+            points[i][1] + side * radius * normals[i][1],
+        ]
+    };
+    let mut polygon: Vec<[f64; 2]> = (0..n).map(|i| offset(i, -1.0)).collect();
+    let end = [-normals[n - 1][0], -normals[n - 1][1]];
+    polygon.extend(half_circle(points[n - 1], end, radius));
+    polygon.extend((0..n).rev().map(|i| offset(i, 1.0)));
+    polygon.extend(half_circle(points[0], normals[0], radius));
+    polygon.push(polygon[0]);
+    polygon
+}
+
+/// Returns the points of the half circle around `center` turning counterclockwise from the direction `from`,
+/// excluding both ends.
+fn half_circle(center: [f64; 2], from: [f64; 2], radius: f64) -> Vec<[f64; 2]> {
+    let angle = from[1].atan2(from[0]);
+    (1..8)
+        .map(|k| {
+            let a = angle + std::f64::consts::PI * k as f64 / 8.0;
+            [center[0] + radius * a.cos(), center[1] + radius * a.sin()]
+        })
+        .collect()
+}
+
+/// Writes the corridor of each edge as a polygon feature in EPSG 4326 to `corridors.geojson`.
+pub fn write_corridors(graph: &Graph, folder: &Path) -> Result<()> {
+    let features = graph
+        .edge_references()
+        .enumerate()
+        .filter(|(_, edge)| !edge.weight().2.spread.corridor.is_empty())
+        .map(|(i, edge)| {
+            let spread = &edge.weight().2.spread;
+            let ring = spread
+                .corridor
+                .iter()
+                .map(|[x, y]| {
+                    let [lat, lon, _] = from_epsg_3857_to_4326(&[*x, *y, 0.0]);
+                    [lon, lat]
+                })
+                .collect_vec();
+            json!({
+                "type": "Feature",
+                "geometry": { "type": "Polygon", "coordinates": [ring] },
+                "properties": {
+                    "label": i,
+                    "source": edge.source().index(),
+                    "target": edge.target().index(),
+                    "weight": edge.weight().0,
+                    "spread_mean": spread.mean_meters,
+                    "spread_percentile": spread.percentile_meters,
+                }
+            })
+        })
+        .collect_vec();
+    let collection = json!({ "type": "FeatureCollection", "features": features });
+    let mut f = File::create(folder.join("corridors.geojson"))?;
+    f.write_all(collection.to_string().as_bytes())?;
+    Ok(())
+}
+
+#[cfg(test)]
+mod test {
+    use super::*;
+
+    #[test]
+    fn tight_and_loose_routes() {
+        let representative = [[0., 0., 0.], [1000., 0., 100.]];
+        let tight: Vec<[f64; 3]> = (0..=10).map(|i| [i as f64 * 100.0, 5.0, 0.0]).collect();
+        let loose: Vec<[f64; 3]> = (0..=10)
+            .map(|i| [i as f64 * 100.0, 200.0 * (i % 2) as f64, 0.0])
+            .collect();
+        let none = Spread::default();
+        let spread = Spread::new(&representative, &[(1, &tight, &none)]);
+        assert_eq!(spread.mean_meters, 5.0);
+        assert_eq!(spread.percentile_meters, 5.0);
+        let spread = Spread::new(&representative, &[(3, &tight, &none), (1, &loose, &none)]);
+        assert!(spread.mean_meters > 5.0);
+        assert_eq!(spread.percentile_meters, 200.0);
+        // The corridor is closed and spans the route
+        let corridor = &spread.corridor;
+        assert_eq!(corridor[0], corridor[corridor.len() - 1]);
+        let max_y = corridor.iter().map(|[_, y]| *y).fold(f64::MIN, f64::max);
+        let min_x = corridor.iter().map(|[x, _]| *x).fold(f64::MAX, f64::min);
+        assert!((max_y - 200.0).abs() < 1e-9);
+        assert!((min_x + 200.0).abs() < 1e-9);
+    }
+
+    #[test]
+    fn merge_spread_of_representatives() {
+        let representative = [[0., 0., 0.], [1000., 0., 100.]];
+        let tight: Vec<[f64; 3]> = (0..=10).map(|i| [i as f64 * 100.0, 5.0, 0.0]).collect();
+        let loose: Vec<[f64; 3]> = (0..=10)
+            .map(|i| [i as f64 * 100.0, 200.0 * (i % 2) as f64, 0.0])
+            .collect();
+        let none = Spread::default();
+        let previous = Spread::new(&representative, &[(3, &tight, &none), (1, &loose, &none)]);
+        // The previous representative stands in for its four trajectories rather than for four copies of itself
+        let extended = Spread::new(
+            &representative,
+            &[(4, &representative, &previous), (1, &tight, &none)],
+        );
+        let direct = Spread::new(&representative, &[(4, &tight, &none), (1, &loose, &none)]);
+        assert_eq!(extended, direct);
+        // Extending again with the same representative keeps the spread
+        let again = Spread::new(&representative, &[(5, &representative, &extended)]);
+        assert_eq!(again, extended);
+    }
+
+    #[test]
+    fn summarise_keeps_mean() {
+        let dists: Vec<[f64; 2]> = (0..1000)
+            .map(|i| [i as f64, 1.0 + (i % 3) as f64])
+            .collect();
+        let mean = |dists: &[[f64; 2]]| {
+            dists.iter().map(|[d, w]| d * w).sum::<f64>()
+                / dists.iter().map(|[_, w]| w).sum::<f64>()
+        };
+        let summary = summarise(dists.clone());
+        assert!(summary.len() <= SUMMARY_SIZE);
+        assert!((mean(&summary) - mean(&dists)).abs() < 1e-9);
+        assert!(summary.windows(2).all(|pair| pair[0][0] < pair[1][0]));
+    }
+
+    #[test]
+    fn corridor_keeps_width_around_turns() {
+        let line = [[0., 0., 0.], [100., 0., 10.], [100., 100., 20.]];
+        let corridor = buffer(&line, 10.0);
+        // The offset vertices at the turn lie 10 meters from the lines of both segments
+        assert!(corridor.contains(&[110.0, -10.0]));
+        assert!(corridor.contains(&[90.0, 10.0]));
+        for [x, y] in corridor {
+            let d = distance_to_polyline(&[x, y, 0.0], &line);
+            assert!(d > 10.0 - 1e-9);
+        }
+    }
+}
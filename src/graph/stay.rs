@@ -0,0 +1,156 @@
+use super::{path_builder::get_paths, PathBuilderStats};
+use crate::from_epsg_3857_to_4326;
+use crate::parser::StreamId;
+use crate::utility::{csv_field, Bbox};
+use itertools::Itertools;
+use serde_json::json;
+use std::fs::File;
+use std::io::{BufWriter, Result, Write};
+
+/// A place where the object stayed, i.e. a [stop](super::PathElement::Stop) found by the `PathBuilder`.
+#[derive(Clone, Debug)]
+pub struct Stay {
+    pub bbox: Bbox,
+    /// Number of points of the stream within `bbox`.
+    pub points: usize,
+}
+
+impl Stay {
+    /// Returns the spatial center of the stay in EPSG 3857.
+    pub fn center(&self) -> [f64; 2] {
+        [
+            (self.bbox.x1 + self.bbox.x2) / 2.0,
+            (self.bbox.y1 + self.bbox.y2) / 2.0,
+        ]
+    }
+
+    pub fn arrival(&self) -> f64 {
+        self.bbox.t1
+    }
+
+    pub fn departure(&self) -> f64 {
+        self.bbox.t2
+    }
+
+    /// Returns the duration of the stay in ms.
+    pub fn duration(&self) -> f64 {
+        self.bbox.t2 - self.bbox.t1
+    }
+}
+
+/// Returns the stays detected in the stream.
+pub fn get_stays(stream: &[[f64; 3]], stats: &mut PathBuilderStats) -> Vec<Stay> {
+    get_paths(stream.to_vec(), stats)
+        .iter()
+        .flat_map(|path| path.get_stops())
+        .map(|bbox| Stay {
+            bbox,
+            points: stream.iter().filter(|pt| bbox.contains_point(pt)).count(),
+        })
+        .collect()
+}
+
+/// Writes the stays of every stream to `stays.csv` and `stays.geojson`.
+///
+/// Times are given in ms after midnight. The csv file holds both EPSG 4326 and EPSG 3857 coordinates,
+/// while the GeoJSON file holds a point feature for each stay in EPSG 4326.
+pub fn write_stays(streams: &[(StreamId, Vec<[f64; 3]>)]) -> Result<()> {
+    let mut stats = PathBuilderStats::default();
+    let stays = streams
+        .iter()
+        .map(|(id, stream)| (id, get_stays(stream, &mut stats)))
+        .collect_vec();
+
+    let rows = stays
+        .iter()
+        .flat_map(|(id, stays)| {
+            stays.iter().enumerate().map(move |(idx, stay)| {
+                let [x, y] = stay.center();
+                let [lat, lon, _] = from_epsg_3857_to_4326(&[x, y, 0.0]);
+                format!(
+                    "{},{},{},{},{},{},{},{},{},{},{},{},{},{},{},{}",
+                    csv_field(&id.source),
+                    csv_field(&id.object),
+                    id.day,
+                    idx,
+                    lat,
+                    lon,
+                    x,
+                    y,
+                    stay.arrival(),
+                    stay.departure(),
+                    stay.duration(),
+                    stay.points,
+                    stay.bbox.x1,
+                    stay.bbox.y1,
+                    stay.bbox.x2,
+                    stay.bbox.y2
+                )
+            })
+        })
+        .join("\n");
+    let f = File::create("stays.csv")?;
+    let mut f = BufWriter::new(f);
+    writeln!(
+        f,
+        "source,object,day,stay,lat,lon,x,y,arrival,departure,duration,points,x1,y1,x2,y2\n{}",
+        rows
+    )?;
+
+    let features = stays
+        .iter()
+        .flat_map(|(id, stays)| {
+            stays.iter().enumerate().map(move |(idx, stay)| {
+                let [x, y] = stay.center();
+                let [lat, lon, _] = from_epsg_3857_to_4326(&[x, y, 0.0]);
+                json!({
+                    "type": "Feature",
+                    "geometry": { "type": "Point", "coordinates": [lon, lat] },
+                    "properties": {
+                        "source": id.source,
+                        "object": id.object,
+                        "day": id.day,
+                        "stay": idx,
+                        "arrival": stay.arrival(),
+                        "departure": stay.departure(),
+                        "duration": stay.duration(),
+                        "points": stay.points,
+                    }
+                })
+            })
+        })
+        .collect_vec();
+    let collection = json!({ "type": "FeatureCollection", "features": features });
+    let mut f = File::create("stays.geojson")?;
+    f.write_all(collection.to_string().as_bytes())?;
+    Ok(())
+}
+
+#[cfg(test)]
+mod test {
+    use super::*;
+
+    const MINUTE: f64 = 60.0 * 1000.0;
+
+    #[test]
+    fn stays_at_both_ends_of_a_route() {
+        // An hour at (0,0), half an hour moving 3km east, then an hour at (3000,0)
+        let mut stream = vec![];
+        for i in 0..60 {
+            stream.push([(i % 3) as f64, 0.0, i as f64 * MINUTE]);
+        }
+        for i in 0..30 {
+            stream.push([i as f64 * 100.0, 0.0, (60 + i) as f64 * MINUTE]);
+        }
+        for i in 0..60 {
+            stream.push([3000.0 + (i % 3) as f64, 0.0, (90 + i) as f64 * MINUTE]);
+        }
+        let mut stats = PathBuilderStats::default();
+        let stays = get_stays(&stream, &mut stats);
+        assert_eq!(stays.len(), 2);
+        assert!(stays[0].center()[0] < 50.0);
+        assert!(stays[1].center()[0] > 2950.0);
+        assert!(stays[0].departure() < stays[1].arrival());
+        assert!(stays.iter().all(|stay| stay.points >= 50));
+    }
+}
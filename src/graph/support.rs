@@ -0,0 +1,60 @@
+use serde::Serialize;
+use std::collections::HashSet;
+
+use crate::parser::StreamId;
+
+/// How many distinct objects and days support a node or an edge.
+#[derive(Clone, Debug, Default, PartialEq, Eq, Serialize)]
+pub struct Support {
+    /// Number of distinct [objects](StreamId::object_name).
+    pub objects: usize,
+    /// Number of distinct calendar days, regardless of the object.
+    pub days: usize,
+}
+
+impl Support {
+    /// Returns the support of the streams, which may contain the same stream several times.
+    pub fn new<'a>(streams: impl IntoIterator<Item = &'a StreamId>) -> Self {
+        let mut objects = HashSet::new();
+        let mut days = HashSet::new();
+        for stream in streams {
+            objects.insert(stream.object_name());
+            days.insert(stream.day.as_str());
+        }
+        Support {
+            objects: objects.len(),
+            days: days.len(),
+        }
+    }
+}
+
+#[cfg(test)]
+mod test {
+    use super::*;
+
+    #[test]
+    fn count_distinct_objects_and_days() {
+        let id = |source: &str, object: &str, day: &str| StreamId {
+            source: source.to_string(),
+            object: object.to_string(),
+            day: day.to_string(),
+        };
+        // Two vessels in one file, and a user identified by their file, on two days
+        let streams = vec![
+            id("ais.csv", "257000001", "2023-01-02"),
+            id("ais.csv", "257000001", "2023-01-02"),
+            id("ais.csv", "257000002", "2023-01-02"),
+            id("data/anna.gpx", "", "2023-01-03"),
+            id("other/anna.gpx", "", "2023-01-03"),
+        ];
+        assert_eq!(streams[3].object_name(), "anna");
+        assert_eq!(
+            Support::new(&streams),
+            Support {
+                objects: 3,
+                days: 2
+            }
+        );
+        assert_eq!(Support::new(&[]), Support::default());
+    }
+}
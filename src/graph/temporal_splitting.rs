@@ -1,84 +1,194 @@
-use std::collections::HashSet;
-
 use petgraph::graph::NodeIndex;
 use petgraph::visit::EdgeRef;
 use petgraph::EdgeDirection;
+use rayon::prelude::*;
 
-use crate::utility::{trajectory::Trajectory, Bbox};
+use crate::utility::{cyclic_time, trajectory::Trajectory, Bbox};
+use crate::STATS;
 
-use super::Graph;
+use super::{Graph, NodeInfo, Visit};
 
+/// Splits nodes temporally s.t. every trajectory starts in an earlier node than the one it ends in.
+///
+/// Each node is split into the fewest temporal slices s.t. every trajectory arriving in a slice arrives before
+/// every trajectory departing from it, see [get_temporal_splits]. Since time increases along every trajectory,
+/// it then increases along every path of the graph, so the graph is acyclic.
+/// Slices keep the [place](super::NodeInfo::place) of the node they were split from.
+///
+/// Nodes wrapping around the end of the [period](cyclic_time::period) are first split at the end of the period,
+/// since every stream starts anew at the beginning of the period.
 pub fn make_temporally_monotone(graph: &mut Graph) {
-    // Try to find suitable temporal split values for each node
-    // based on start and end times of trajectories connecting them.
-    let mut required_splits = vec![];
-    for nx in graph.node_indices() {
-        // calculate required splits
-        let mut edges: Vec<Trajectory> = graph
-            .edges_directed(nx, EdgeDirection::Incoming)
-            .map(|edge| edge.weight().1.clone())
-            .collect();
-        let mut outgoing: Vec<Trajectory> = graph
-            .edges_directed(nx, EdgeDirection::Outgoing)
-            .map(|edge| edge.weight().1.clone())
-            .collect();
-        edges.append(&mut outgoing);
-        let splits = get_temporal_splits(edges);
-        required_splits.push((nx, splits));
+    // Nodes that weren't split before are places of their own
+    let mut next_place = graph
+        .node_weights()
+        .filter_map(|(_, _, info)| info.place)
+        .max()
+        .map_or(0, |place| place + 1);
+    let nodes: Vec<NodeIndex> = graph.node_indices().collect();
+    for nx in nodes.iter() {
+        if graph[*nx].2.place.is_none() {
+            graph[*nx].2.place = Some(next_place);
+            next_place += 1;
+        }
     }
-    for (split_node, splits) in required_splits {
-        // Split bbox at splits and add the new nodes
-        let nodes: Vec<NodeIndex> = split_bbox(graph[split_node].1, &splits)
-            .into_iter()
-            .map(|bbox| graph.add_node((graph[split_node].0, bbox)))
-            .collect();
-        reassign_edges(graph, split_node, &nodes, EdgeDirection::Outgoing);
-        reassign_edges(graph, split_node, &nodes, EdgeDirection::Incoming);
-        // remove new nodes that has no edges
-        let no_edge_nodes: Vec<&NodeIndex> = nodes
-            .iter()
-            .filter(|nx| {
-                (graph.edges_directed(**nx, EdgeDirection::Incoming).count() == 0)
-                    & (graph.edges_directed(**nx, EdgeDirection::Outgoing).count() == 0)
-            })
-            .collect();
-        for nx in no_edge_nodes.iter() {
-            graph.remove_node(**nx);
+    let period = cyclic_time::period();
+    let mut boundary_splits = 0;
+    for nx in nodes {
+        let bbox = &mut graph[nx].1;
+        let shift = (bbox.t1 / period).floor() * period;
+        bbox.t1 -= shift;
+        bbox.t2 -= shift;
+        if bbox.t2 >= period {
+            boundary_splits += 1;
+            for slice in split_node(graph, nx, &[period - 1.0], period) {
+                // Move the part after the end of the period back to its beginning
+                let bbox = &mut graph[slice].1;
+                if bbox.t1 >= period {
+                    bbox.t1 -= period;
+                    bbox.t2 -= period;
+                }
+            }
         }
-        // Finally remove the split node
-        graph.remove_node(split_node);
+    }
+    // Splits are found for each node independently and in parallel,
+    // based on the departures and arrivals of the trajectories connecting them.
+    let nodes: Vec<NodeIndex> = graph.node_indices().collect();
+    let required_splits: Vec<(NodeIndex, Vec<f64>)> = {
+        let graph: &Graph = graph;
+        nodes
+            .into_par_iter()
+            .map(|nx| (nx, required_splits(graph, nx)))
+            .filter(|(_, splits)| !splits.is_empty())
+            .collect()
+    };
+    STATS.lock().unwrap().node_splits += boundary_splits
+        + required_splits
+            .iter()
+            .map(|(_, splits)| splits.len())
+            .sum::<usize>();
+    for (nx, splits) in required_splits {
+        split_node(graph, nx, &splits, period);
     }
 }
 
-/// Returns list of timestamps where the trajectories should split to avoid breaking temporal monotonicity
-fn get_temporal_splits(trjs: Vec<Trajectory>) -> Vec<f64> {
-    let mut splits = vec![];
-    let mut timestamps = vec![];
-    for (idx, trj) in trjs.iter().enumerate() {
-        let (t1, t2) = (trj[0][2], trj[trj.len() - 1][2]);
-        timestamps.push((idx, t1));
-        timestamps.push((idx, t2));
+/// Returns the splits of the node given the departures of its outgoing edges and the arrivals of its incoming edges.
+/// A self-loop both departs from and arrives in the node.
+fn required_splits(graph: &Graph, nx: NodeIndex) -> Vec<f64> {
+    let departures: Vec<f64> = graph
+        .edges_directed(nx, EdgeDirection::Outgoing)
+        .map(|edge| edge.weight().1[0][2])
+        .collect();
+    let arrivals: Vec<f64> = graph
+        .edges_directed(nx, EdgeDirection::Incoming)
+        .map(|edge| {
+            let trj: &Trajectory = &edge.weight().1;
+            trj[trj.len() - 1][2]
+        })
+        .collect();
+    get_temporal_splits(&graph[nx].1, &departures, &arrivals)
+}
+
+/// Replaces the node by temporal slices split at `splits` and returns the slices that have edges.
+fn split_node(
+    graph: &mut Graph,
+    split_node: NodeIndex,
+    splits: &[f64],
+    period: f64,
+) -> Vec<NodeIndex> {
+    // Split bbox at splits and add the new nodes
+    let (weight, bbox, info) = graph[split_node].clone();
+    let nodes: Vec<NodeIndex> = split_bbox(bbox, splits)
+        .into_iter()
+        .map(|bbox| {
+            let info = NodeInfo {
+                visits: vec![],
+                ..info.clone()
+            };
+            graph.add_node((weight, bbox, info))
+        })
+        .collect();
+    // Arrivals and departures belong to the slice they occur in, like the edges they arrive and depart with.
+    // A visit whose arrival and departure fall in different slices is split in two, both keeping its dwell time.
+    let slice_of = |t: f64| {
+        let t = cyclic_time::unwrap_into(t, bbox.t1, bbox.t2, period);
+        splits.iter().filter(|split| **split < t).count()
+    };
+    for visit in info.visits {
+        let arrival = visit.arrival.map(slice_of);
+        let departure = visit.departure.map(slice_of);
+        match (arrival, departure) {
+            (Some(arrival), Some(departure)) if arrival != departure => {
+                let arriving = Visit {
+                    departure: None,
+                    departing: None,
+                    dwell: visit.dwell_time(),
+                    ..visit.clone()
+                };
+                graph[nodes[arrival]].2.visits.push(arriving);
+                let departing = Visit {
+                    arrival: None,
+                    arriving: None,
+                    dwell: visit.dwell_time(),
+                    ..visit
+                };
+                graph[nodes[departure]].2.visits.push(departing);
+            }
+            _ => {
+                let slice = arrival.or(departure).unwrap();
+                graph[nodes[slice]].2.visits.push(visit);
+            }
+        }
     }
-    timestamps.sort_by(|(_, a), (_, b)| a.partial_cmp(b).unwrap());
-    let mut visited: HashSet<usize> = HashSet::new();
-    let mut last_visited = None;
-    for (idx, t) in timestamps.into_iter() {
-        // mark index as visited
-        if !visited.insert(idx) {
-            // if we already visited the edge we can infer
-            // that this must be a split.
-            // (see paper on detour, section on 'Perserving temporal monotonicity')
-            // we add -1ms s.t. the split is just before the end
-            // of the edge
-            if let Some(last_visited) = last_visited {
-                splits.push(last_visited);
-                splits.push(t - 1.0);
-                visited = HashSet::new();
-            } else {
-                panic!("This can't happen since we must've visited the idx.")
+    reassign_edges(graph, split_node, &nodes, EdgeDirection::Outgoing, period);
+    reassign_edges(graph, split_node, &nodes, EdgeDirection::Incoming, period);
+    // Finally remove the split node
+    graph.remove_node(split_node);
+    // remove new nodes that has no edges
+    nodes
+        .into_iter()
+        .filter(|nx| {
+            let no_edges = (graph.edges_directed(*nx, EdgeDirection::Incoming).count() == 0)
+                & (graph.edges_directed(*nx, EdgeDirection::Outgoing).count() == 0);
+            if no_edges {
+                graph.remove_node(*nx);
             }
+            !no_edges
+        })
+        .collect()
+}
+
+/// Returns the fewest timestamps where the node should be split s.t. no trajectory departs from a slice
+/// before another trajectory arrives in it, given the departures and arrivals of the node.
+///
+/// Splitting at `x` separates `[t1, x]` from `[x+1ms, t2]`, so a departure at `d` followed by an arrival at `a`
+/// requires a split in `[d, a-1ms]`. This includes round trips, which would otherwise be self-loops.
+/// It suffices to separate each departure from the first arrival after it, since later arrivals are then separated too.
+/// Times outside the box belong to other slices of the place, which are already split apart.
+/// Finding the fewest splits is thus the interval stabbing problem, which is solved optimally by greedily
+/// splitting at the end of the interval ending first among those not yet split.
+/// Note that this is the fewest splits making every slice monotone on its own,
+/// which may exceed the fewest splits making the graph acyclic when no path leads from a departure back to the node.
+fn get_temporal_splits(bbox: &Bbox, departures: &[f64], arrivals: &[f64]) -> Vec<f64> {
+    let within = |t: &&f64| (bbox.t1 <= **t) & (**t <= bbox.t2);
+    let arrivals: Vec<f64> = arrivals.iter().filter(within).copied().collect();
+    let mut intervals: Vec<(f64, f64)> = departures
+        .iter()
+        .filter(within)
+        .filter_map(|departure| {
+            arrivals
+                .iter()
+                .filter(|arrival| *arrival > departure)
+                .min_by(|a, b| a.partial_cmp(b).unwrap())
+                .map(|arrival| (*departure, arrival - 1.0))
+        })
+        .filter(|(start, last)| start <= last)
+        .collect();
+    intervals.sort_by(|(_, a), (_, b)| a.partial_cmp(b).unwrap());
+    let mut splits: Vec<f64> = vec![];
+    for (start, last) in intervals {
+        if splits.last().is_none_or(|split| *split < start) {
+            splits.push(last);
         }
-        last_visited = Some(t + 1.0);
     }
     splits
 }
@@ -100,7 +210,12 @@ fn reassign_edges(
     split_node: NodeIndex,
     nodes: &[NodeIndex],
     direction: EdgeDirection,
+    period: f64,
 ) {
+    let in_temporal = |nx: NodeIndex, point: &[f64; 3]| {
+        let bbox = graph[nx].1;
+        cyclic_time::contains(bbox.t1, bbox.t2, point[2], period)
+    };
     let mut rm_edge_idx = vec![];
     let edges = match direction {
         EdgeDirection::Outgoing => {
@@ -116,7 +231,7 @@ fn reassign_edges(
                     let mut target = None;
                     let end_point = trj.1[trj.1.len() - 1];
                     for node in nodes {
-                        if graph[*node].1.is_in_temporal(&end_point) {
+                        if in_temporal(*node, &end_point) {
                             target = Some(*node);
                         }
                     }
@@ -127,7 +242,7 @@ fn reassign_edges(
                     }
                 };
                 for node in nodes {
-                    if graph[*node].1.is_in_temporal(&start_point) {
+                    if in_temporal(*node, &start_point) {
                         edges.push((*node, target, trj));
                         break;
                     }
@@ -148,7 +263,7 @@ fn reassign_edges(
                     let mut source = None;
                     let start_point = trj.1[0];
                     for node in nodes {
-                        if graph[*node].1.is_in_temporal(&start_point) {
+                        if in_temporal(*node, &start_point) {
                             source = Some(*node);
                         }
                     }
@@ -159,7 +274,7 @@ fn reassign_edges(
                     }
                 };
                 for node in nodes {
-                    if graph[*node].1.is_in_temporal(&end_point) {
+                    if in_temporal(*node, &end_point) {
                         edges.push((source, *node, trj));
                         break;
                     }
@@ -175,3 +290,139 @@ fn reassign_edges(
         graph.remove_edge(ex);
     }
 }
+
+#[cfg(test)]
+mod test {
+    use super::*;
+    use crate::graph::EdgeInfo;
+    use crate::parser::StreamId;
+    use petgraph::algo::is_cyclic_directed;
+
+    fn bbox(x: f64, t1: f64, t2: f64) -> Bbox {
+        Bbox {
+            x1: x,
+            x2: x + 10.0,
+            y1: 0.0,
+            y2: 10.0,
+            t1,
+            t2,
+        }
+    }
+
+    #[test]
+    fn fewest_splits_separate_departures_from_later_arrivals() {
+        let departures = [0.0, 5.0, 20.0, 50.0, 95.0];
+        let arrivals = [10.0, 15.0, 30.0, 200.0];
+        let splits = get_temporal_splits(&bbox(0.0, 0.0, 100.0), &departures, &arrivals);
+        // The departure at 50 is followed by no arrival within the box, nor is the one at 95
+        assert_eq!(splits, vec![9.0, 29.0]);
+        // Arriving before departing requires no split
+        let splits = get_temporal_splits(&bbox(0.0, 0.0, 100.0), &[60.0], &[10.0, 60.0]);
+        assert!(splits.is_empty());
+    }
+
+    #[test]
+    fn split_self_loop_between_departure_and_arrival() {
+        // A jog leaving home and returning to it
+        let mut graph = Graph::new();
+        let home = graph.add_node((2, bbox(0.0, 0.0, 100.0), NodeInfo::default()));
+        let jog = vec![[5.0, 5.0, 20.0], [300.0, 5.0, 40.0], [5.0, 5.0, 60.0]];
+        graph.add_edge(home, home, (1, jog.clone(), EdgeInfo::new(&jog, false)));
+        let visit = |arrival, departure| Visit {
+            stream: StreamId::default(),
+            arrival,
+            departure,
+            arriving: None,
+            departing: None,
+            dwell: None,
+        };
+        graph[home].2.visits = vec![visit(None, Some(20.0)), visit(Some(60.0), None)];
+        make_temporally_monotone(&mut graph);
+        assert_eq!(graph.node_count(), 2);
+        let ex = graph.edge_indices().next().unwrap();
+        let (source, target) = graph.edge_endpoints(ex).unwrap();
+        assert_ne!(source, target);
+        assert!(graph[source].1.is_before(&graph[target].1));
+        assert_eq!(graph[source].2.place, graph[target].2.place);
+        // Each slice keeps the visit within its timespan
+        assert_eq!(graph[source].2.visits, vec![visit(None, Some(20.0))]);
+        assert_eq!(graph[target].2.visits, vec![visit(Some(60.0), None)]);
+    }
+
+    #[test]
+    fn split_visit_between_arrival_and_departure() {
+        // A jog from home splits it while another object stays from before the jog until after it
+        let mut graph = Graph::new();
+        let home = graph.add_node((3, bbox(0.0, 0.0, 100.0), NodeInfo::default()));
+        let b = graph.add_node((1, bbox(500.0, 75.0, 90.0), NodeInfo::default()));
+        let jog = vec![[5.0, 5.0, 20.0], [300.0, 5.0, 40.0], [5.0, 5.0, 60.0]];
+        let away = vec![[5.0, 5.0, 70.0], [505.0, 5.0, 80.0]];
+        graph.add_edge(home, home, (1, jog.clone(), EdgeInfo::new(&jog, false)));
+        graph.add_edge(home, b, (1, away.clone(), EdgeInfo::new(&away, false)));
+        let stay = Visit {
+            stream: StreamId::default(),
+            arrival: Some(10.0),
+            departure: Some(70.0),
+            arriving: Some(0),
+            departing: Some(1),
+            dwell: None,
+        };
+        graph[home].2.visits = vec![stay.clone()];
+        make_temporally_monotone(&mut graph);
+        let (source, _) = graph
+            .edge_indices()
+            .map(|ex| graph.edge_endpoints(ex).unwrap())
+            .find(|(_, target)| *target == b)
+            .unwrap();
+        // The departure stays with the edge the object departs with
+        let departing = Visit {
+            arrival: None,
+            arriving: None,
+            dwell: Some(60.0),
+            ..stay.clone()
+        };
+        assert_eq!(graph[source].2.visits, vec![departing]);
+        let arriving = Visit {
+            departure: None,
+            departing: None,
+            dwell: Some(60.0),
+            ..stay
+        };
+        let arrival_slice = graph
+            .node_indices()
+            .find(|nx| *nx != source && *nx != b)
+            .unwrap();
+        assert!(graph[arrival_slice].1.is_before(&graph[source].1));
+        assert_eq!(graph[arrival_slice].2.visits, vec![arriving]);
+        // Both slices report the full stay
+        assert_eq!(graph[source].2.dwell_times(), vec![60.0]);
+        assert_eq!(graph[arrival_slice].2.dwell_times(), vec![60.0]);
+    }
+
+    #[test]
+    fn split_round_trip_into_slices_of_same_place() {
+        // From a to b and back again
+        let mut graph = Graph::new();
+        let a = graph.add_node((2, bbox(0.0, 0.0, 100.0), NodeInfo::default()));
+        let b = graph.add_node((2, bbox(500.0, 40.0, 70.0), NodeInfo::default()));
+        let there = vec![[5.0, 5.0, 10.0], [505.0, 5.0, 50.0]];
+        let back = vec![[505.0, 5.0, 60.0], [5.0, 5.0, 90.0]];
+        graph.add_edge(a, b, (1, there.clone(), EdgeInfo::new(&there, false)));
+        graph.add_edge(b, a, (1, back.clone(), EdgeInfo::new(&back, false)));
+        assert!(is_cyclic_directed(&graph));
+        // A single split between leaving a and returning to it suffices
+        assert_eq!(required_splits(&graph, a).len(), 1);
+        assert!(required_splits(&graph, b).is_empty());
+        make_temporally_monotone(&mut graph);
+        assert!(!is_cyclic_directed(&graph));
+        assert_eq!(graph.node_count(), 3);
+        assert_eq!(graph.edge_count(), 2);
+        let places: Vec<usize> = graph
+            .node_weights()
+            .filter(|(_, bbox, _)| bbox.x1 == 0.0)
+            .map(|(_, _, info)| info.place.unwrap())
+            .collect();
+        assert_eq!(places.len(), 2);
+        assert_eq!(places[0], places[1]);
+    }
+}
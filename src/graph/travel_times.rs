@@ -0,0 +1,189 @@
+use itertools::Itertools;
+use petgraph::visit::IntoEdgeReferences;
+use serde::{Deserialize, Serialize};
+use std::fs::File;
+use std::io::{BufWriter, Result, Write};
+use std::path::Path;
+
+use super::Graph;
+use crate::CONFIG;
+
+/// Departure and arrival times of the trajectories an edge represents, see [cyclic_time](crate::utility::cyclic_time).
+#[derive(Clone, Debug, Default, PartialEq, Serialize, Deserialize)]
+#[serde(default)]
+pub struct TravelTimes {
+    pub departures: Vec<f64>,
+    pub arrivals: Vec<f64>,
+}
+
+impl TravelTimes {
+    /// Returns the travel times of a single trajectory.
+    pub fn new(trj: &[[f64; 3]]) -> Self {
+        TravelTimes {
+            departures: vec![trj[0][2]],
+            arrivals: vec![trj[trj.len() - 1][2]],
+        }
+    }
+
+    /// Adds the travel times of `other` to `self`.
+    pub fn merge(&mut self, other: &TravelTimes) {
+        self.departures.extend(other.departures.iter());
+        self.arrivals.extend(other.arrivals.iter());
+    }
+
+    /// Returns the time each trajectory took from departure to arrival.
+    pub fn durations(&self) -> Vec<f64> {
+        self.departures
+            .iter()
+            .zip(self.arrivals.iter())
+            .map(|(departure, arrival)| arrival - departure)
+            .collect()
+    }
+}
+
+/// Summary of a sample of times in ms.
+#[derive(Clone, Debug, PartialEq, Serialize)]
+pub struct Distribution {
+    pub min: f64,
+    pub p10: f64,
+    pub p25: f64,
+    pub median: f64,
+    pub p75: f64,
+    pub p90: f64,
+    pub max: f64,
+    /// Number of times in each bin of the given width, starting at zero.
+    pub histogram: Vec<u32>,
+}
+
+impl Distribution {
+    /// Returns the distribution of `values` using the nearest rank percentiles, or None if there are no values.
+    pub fn new(values: &[f64], bin: f64) -> Option<Self> {
+        if values.is_empty() {
+            return None;
+        }
+        let mut sorted = values.to_vec();
+        sorted.sort_by(|a, b| a.partial_cmp(b).unwrap());
+        let percentile = |p: f64| {
+            let rank = (p * sorted.len() as f64).ceil() as usize;
+            sorted[rank.max(1) - 1]
+        };
+        let max = sorted[sorted.len() - 1];
+        let mut histogram = vec![0; (max.max(0.0) / bin).floor() as usize + 1];
+        for v in sorted.iter() {
+            histogram[(v.max(0.0) / bin).floor() as usize] += 1;
+        }
+        Some(Distribution {
+            min: sorted[0],
+            p10: percentile(0.1),
+            p25: percentile(0.25),
+            median: percentile(0.5),
+            p75: percentile(0.75),
+            p90: percentile(0.9),
+            max,
+            histogram,
+        })
+    }
+}
+
+/// Summarizes the departure times, arrival times and durations of each edge in `edge_times.csv`.
+///
+/// Histogram counts are separated by spaces, using bins of an hour for departures and arrivals.
+pub fn write_edge_times(graph: &Graph, folder: &Path) -> Result<()> {
+    let hour = 60.0 * 60.0 * 1000.0;
+    let duration_bin = CONFIG.duration_bin_minutes * 60.0 * 1000.0;
+    let times = graph
+        .edge_references()
+        .enumerate()
+        .flat_map(|(i, edge)| {
+            let times = &edge.weight().2.times;
+            vec![
+                ("departure", Distribution::new(&times.departures, hour)),
+                ("arrival", Distribution::new(&times.arrivals, hour)),
+                (
+                    "duration",
+                    Distribution::new(&times.durations(), duration_bin),
+                ),
+            ]
+            .into_iter()
+            .filter_map(move |(measure, distribution)| {
+                distribution.map(|d| {
+                    format!(
+                        "{},{},{},{},{},{},{},{},{},{}",
+                        i,
+                        measure,
+                        d.min,
+                        d.p10,
+                        d.p25,
+                        d.median,
+                        d.p75,
+                        d.p90,
+                        d.max,
+                        d.histogram.iter().join(" ")
+                    )
+                })
+            })
+        })
+        .join("\n");
+    let f = File::create(folder.join("edge_times.csv"))?;
+    let mut f = BufWriter::new(f);
+    writeln!(
+        f,
+        "label,measure,min,p10,p25,median,p75,p90,max,histogram\n{}",
+        times
+    )?;
+    Ok(())
+}
+
+#[cfg(test)]
+mod test {
+    use super::*;
+    use crate::graph::{EdgeInfo, NodeInfo};
+    use crate::utility::Bbox;
+
+    const MINUTE: f64 = 60.0 * 1000.0;
+
+    #[test]
+    fn distribution_of_commutes() {
+        let mut times = TravelTimes::default();
+        for (departure, minutes) in [(7.5, 30.0), (8.0, 25.0), (8.25, 35.0), (9.0, 30.0)] {
+            let departure = departure * 60.0 * MINUTE;
+            let trj = [
+                [0.0, 0.0, departure],
+                [0.0, 0.0, departure + minutes * MINUTE],
+            ];
+            times.merge(&TravelTimes::new(&trj));
+        }
+        let durations = Distribution::new(&times.durations(), 10.0 * MINUTE).unwrap();
+        assert_eq!(durations.min, 25.0 * MINUTE);
+        assert_eq!(durations.median, 30.0 * MINUTE);
+        assert_eq!(durations.max, 35.0 * MINUTE);
+        assert_eq!(durations.histogram, vec![0, 0, 1, 3]);
+        let departures = Distribution::new(&times.departures, 60.0 * MINUTE).unwrap();
+        assert_eq!(departures.histogram[7..], [1, 2, 1]);
+        assert_eq!(Distribution::new(&[], MINUTE), None);
+    }
+
+    #[test]
+    fn write_distributions_of_each_edge() {
+        let trj = [[0.0, 0.0, 0.0], [0.0, 0.0, 20.0 * MINUTE]];
+        let mut graph = Graph::new();
+        let a = graph.add_node((1, Bbox::new(&trj), NodeInfo::default()));
+        let b = graph.add_node((1, Bbox::new(&trj), NodeInfo::default()));
+        graph.add_edge(a, b, (1, trj.to_vec(), EdgeInfo::new(&trj, false)));
+        let folder = std::env::temp_dir().join(format!("detour-times-{}", std::process::id()));
+        std::fs::create_dir_all(&folder).unwrap();
+        write_edge_times(&graph, &folder).unwrap();
+        let csv = std::fs::read_to_string(folder.join("edge_times.csv")).unwrap();
+        std::fs::remove_dir_all(&folder).unwrap();
+        let d = 20.0 * MINUTE;
+        assert_eq!(
+            csv.lines().collect::<Vec<_>>(),
+            vec![
+                "label,measure,min,p10,p25,median,p75,p90,max,histogram".to_string(),
+                "0,departure,0,0,0,0,0,0,0,1".to_string(),
+                format!("0,arrival,{d},{d},{d},{d},{d},{d},{d},1"),
+                format!("0,duration,{d},{d},{d},{d},{d},{d},{d},0 0 0 0 1"),
+            ]
+        );
+    }
+}
@@ -0,0 +1,125 @@
+//! Graph validation
+//!
+//! Checks the invariants every graph is expected to satisfy, regardless of the builder that constructed it.
+use petgraph::algo::tarjan_scc;
+use petgraph::visit::{EdgeRef, IntoEdgeReferences};
+use serde::Serialize;
+
+use super::Graph;
+use crate::utility::trajectory::{Monotone, Trajectory};
+
+/// An invariant broken by a graph. Nodes and edges are referred to by their index.
+#[derive(Clone, Debug, PartialEq, Serialize)]
+#[serde(tag = "violation", rename_all = "snake_case")]
+pub enum Violation {
+    /// The nodes form a cycle, or a self-loop if there is only one.
+    Cycle { nodes: Vec<usize> },
+    /// The timestamps of the edge trajectory are not increasing.
+    NonMonotoneTrajectory { edge: usize },
+    /// The box of the target node ends before the box of the source node begins.
+    BackInTime {
+        edge: usize,
+        source: usize,
+        target: usize,
+    },
+    /// The first point of the edge trajectory lies outside the box of its source node.
+    StartOutsideSource { edge: usize, source: usize },
+    /// The last point of the edge trajectory lies outside the box of its target node.
+    EndOutsideTarget { edge: usize, target: usize },
+    /// The node represents no visits.
+    NodeWithoutWeight { node: usize },
+    /// The edge represents no trajectories.
+    EdgeWithoutWeight { edge: usize },
+}
+
+/// Returns every violation of the graph invariants.
+///
+/// A valid graph is acyclic, its edges move forward in time from the box of their source node to the box of their target node,
+/// and every node and edge represents at least one visit or trajectory.
+pub fn validate(graph: &Graph) -> Vec<Violation> {
+    let mut violations = vec![];
+    for component in tarjan_scc(graph) {
+        let self_loop = component.len() == 1 && graph.contains_edge(component[0], component[0]);
+        if component.len() > 1 || self_loop {
+            let mut nodes: Vec<usize> = component.iter().map(|nx| nx.index()).collect();
+            nodes.sort_unstable();
+            violations.push(Violation::Cycle { nodes });
+        }
+    }
+    for nx in graph.node_indices() {
+        if graph[nx].0 == 0 {
+            violations.push(Violation::NodeWithoutWeight { node: nx.index() });
+        }
+    }
+    for edge in graph.edge_references() {
+        let (weight, trj, _) = edge.weight();
+        let (source, target) = (edge.source(), edge.target());
+        let (from, to) = (&graph[source].1, &graph[target].1);
+        let ex = edge.id().index();
+        if *weight == 0 {
+            violations.push(Violation::EdgeWithoutWeight { edge: ex });
+        }
+        if to.is_before(from) {
+            violations.push(Violation::BackInTime {
+                edge: ex,
+                source: source.index(),
+                target: target.index(),
+            });
+        }
+        if trj.is_empty() {
+            continue;
+        }
+        let trajectory: Trajectory = trj.to_vec();
+        if !trajectory.is_monotone() {
+            violations.push(Violation::NonMonotoneTrajectory { edge: ex });
+        }
+        if !from.contains_point(&trj[0]) {
+            violations.push(Violation::StartOutsideSource {
+                edge: ex,
+                source: source.index(),
+            });
+        }
+        if !to.contains_point(&trj[trj.len() - 1]) {
+            violations.push(Violation::EndOutsideTarget {
+                edge: ex,
+                target: target.index(),
+            });
+        }
+    }
+    violations
+}
+
+#[cfg(test)]
+mod test {
+    use super::*;
+    use crate::graph::{EdgeInfo, NodeInfo};
+    use crate::utility::Bbox;
+
+    fn node(x: f64, t1: f64, t2: f64) -> (u32, Bbox, NodeInfo) {
+        let bbox = Bbox::new(&[[x, 0.0, t1], [x + 10.0, 10.0, t2]]);
+        (1, bbox, NodeInfo::default())
+    }
+
+    #[test]
+    fn report_cycles_and_edges_outside_nodes() {
+        let mut graph = Graph::new();
+        let a = graph.add_node(node(0.0, 0.0, 100.0));
+        let b = graph.add_node(node(500.0, 40.0, 70.0));
+        let there = vec![[5.0, 5.0, 10.0], [505.0, 5.0, 50.0]];
+        graph.add_edge(a, b, (1, there.clone(), EdgeInfo::new(&there, false)));
+        assert!(validate(&graph).is_empty());
+
+        let back = vec![[505.0, 5.0, 60.0], [50.0, 5.0, 90.0]];
+        let ex = graph.add_edge(b, a, (0, back.clone(), EdgeInfo::new(&back, false)));
+        let violations = validate(&graph);
+        assert_eq!(violations.len(), 3);
+        assert!(violations.contains(&Violation::Cycle {
+            nodes: vec![a.index(), b.index()]
+        }));
+        assert!(violations.contains(&Violation::EdgeWithoutWeight { edge: ex.index() }));
+        assert!(violations.contains(&Violation::EndOutsideTarget {
+            edge: ex.index(),
+            target: a.index()
+        }));
+    }
+}
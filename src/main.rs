@@ -10,8 +10,24 @@
 //! ``` shell
 //! $ cat /home/user/gpx/* | .detour -o /home/user/output
 //! ```
+//! or pass the files as arguments, s.t. each stream can be traced back to its file:
+//! ``` shell
+//! $ .detour -o /home/user/output /home/user/gpx/*
+//! ```
 //!
 //! To use a specific configuration file use the `-c` or `--config` option followed by desired configuration file.
+//! The graph is built by [get_graph_v2](graph::get_graph_v2) unless `--builder v1` selects [get_graph](graph::get_graph).
+//! A graph written using `--json` can be updated with new data by passing it to `--extend`, see [extend_graph](graph::extend_graph).
+//! With `--day-types` a graph is built for each day type, e.g. weekdays and weekends, see [classify_days](graph::classify_days).
+//! With `--per-object` a graph is built for each object, e.g. each vessel or each user's file, instead of a shared graph.
+//! Either way nodes and edges count the distinct objects and days supporting them, see [Support](graph::Support).
+//! A written graph.json is checked for cycles and edges leaving their nodes using `--validate`, see [validate](graph::validate).
+//! Time of day wraps around at midnight, or at the end of the week if `--weekly` is given, see [cyclic_time](utility::cyclic_time).
+//! With `--nodes` the visits to each node and their dwell times are written as well, see [Visit](graph::Visit).
+//! With `--edges` the spread and travel times of the trajectories of each edge are written as well,
+//! see [Spread](graph::Spread) and [TravelTimes](graph::TravelTimes).
+//! Each input trajectory is then assigned to the edge representing it, see [EdgeInfo](graph::EdgeInfo).
+//! Edges are matched to the roads of a local OpenStreetMap extract given by `--osm`, see [map_matching].
 //! By default output is written to a folder called `Output` unless otherwise is specified by using the `-o` or `--output` option.
 //! ## Configuration
 //! Various settings can be adjusted by modifying config.json located in
@@ -21,33 +37,64 @@ extern crate lazy_static;
 
 pub mod arguments;
 pub mod config;
-use arguments::{ClusteringArgs, Output};
+use arguments::{ClusteringArgs, GraphBuilder, Input, Output};
 pub use config::Config;
-use graph::{get_graph_v2, Writable};
+use graph::{
+    build_graph, classify_days, extend_graph, group_by_day_type, read_graph, read_holidays,
+    validate, write_segmentation, write_stays, Writable,
+};
+use map_matching::RoadNetwork;
+use parser::{group_by_object, DailyStream};
+use rayon::prelude::*;
+use serde::Serialize;
 use std::{
+    collections::BTreeMap,
     io::{BufReader, Read},
+    path::Path,
     sync::Mutex,
 };
 pub use utility::{time_guard, CHFilter, StopDetector};
 mod coord;
 mod graph;
+mod map_matching;
 mod parser;
 mod utility;
-use crate::{graph::get_graph, utility::visvalingam};
+use crate::utility::{csv_field, cyclic_time, folder_names};
 pub use coord::{from_epsg_3857_to_4326, from_epsg_4326_to_3857};
+#[cfg(not(test))]
 lazy_static! {
     pub static ref CONFIG: Config = arguments::parse_arguments();
 }
 
+// Tests must not parse the arguments given to the test harness.
+#[cfg(test)]
+lazy_static! {
+    pub static ref CONFIG: Config = Config::default();
+}
+
+lazy_static! {
+    pub static ref INPUT: Mutex<Input> = Mutex::new(Input::default());
+}
+
 lazy_static! {
     pub static ref OUTPUT: Mutex<Output> = Mutex::new(Output::default());
 }
 
+lazy_static! {
+    /// The road network trajectories are matched to, if an extract is given using `--osm`.
+    pub static ref ROAD_NETWORK: Option<RoadNetwork> = INPUT
+        .lock()
+        .unwrap()
+        .osm
+        .as_ref()
+        .map(|osm| map_matching::read_network(osm));
+}
+
 lazy_static! {
     pub static ref CLUSTERINGARGS: Mutex<ClusteringArgs> = Mutex::new(ClusteringArgs::default());
 }
 
-#[derive(Debug, Default, Clone)]
+#[derive(Debug, Default, Clone, Serialize)]
 pub struct Statistics {
     pub node_merges: usize,
     pub edge_merges: usize,
@@ -67,21 +114,122 @@ fn main() {
         // This bogus test ensures we parse arguments before trying to read from stdin
         // s.t. the user can get a helpful message
     }
-    let mut buf_reader = BufReader::new(std::io::stdin());
-    let mut contents = String::new();
-    buf_reader
-        .read_to_string(&mut contents)
-        .expect("can't read from stdin");
+    let validation = INPUT.lock().unwrap().validate.clone();
+    if let Some(path) = validation {
+        let graph = read_graph(&path).expect("Could not read graph to validate.");
+        let violations = validate(&graph);
+        println!("{}", serde_json::to_string(&violations).unwrap());
+        std::process::exit(if violations.is_empty() { 0 } else { 1 });
+    }
+    let files = INPUT.lock().unwrap().files.clone();
+    let contents: Vec<(String, String)> = if files.is_empty() {
+        let mut buf_reader = BufReader::new(std::io::stdin());
+        let mut contents = String::new();
+        buf_reader
+            .read_to_string(&mut contents)
+            .expect("can't read from stdin");
+        vec![("stdin".to_string(), contents)]
+    } else {
+        files
+            .iter()
+            .map(|file| {
+                let contents = std::fs::read_to_string(file).expect("can't read input file");
+                (file.display().to_string(), contents)
+            })
+            .collect()
+    };
     //println!("Parsing input...");
-    let daily_streams: Vec<Vec<[f64; 3]>> = parser::parse(contents)
-        .into_iter()
-        .filter(|day| !day.is_empty())
-        .map(time_guard::clean_stream)
+    // Inputs are parsed in parallel and split into streams, which are then cleaned in parallel,
+    // keeping the order of the streams. A single input, e.g. stdin, still gets its streams cleaned in parallel.
+    let daily_streams: Vec<DailyStream> = contents
+        .into_par_iter()
+        .flat_map_iter(|(source, contents)| parser::parse(contents, &source))
+        .collect::<Vec<DailyStream>>()
+        .into_par_iter()
+        .filter(|(_, day)| !day.is_empty())
+        .map(|(id, day)| (id, time_guard::clean_stream(day)))
         //.map(|stream| {
         //    CHFilter::new(CONFIG.window_size, stream.into_iter()).collect::<Vec<[f64; 3]>>()
         //})
         .collect();
+    if OUTPUT.lock().unwrap().stays {
+        write_stays(&daily_streams).expect("Could not write stays.");
+    }
+    if OUTPUT.lock().unwrap().segmentation_debug {
+        write_segmentation(&daily_streams).expect("Could not write segmentation.");
+    }
     //println!("Constructing graph...");
-    let graph = get_graph_v2(daily_streams);
+    let builder = CLUSTERINGARGS.lock().unwrap().builder;
+    let day_types = CLUSTERINGARGS.lock().unwrap().day_types;
+    let extend = INPUT.lock().unwrap().graph.clone();
+    if let Some(classification) = day_types {
+        let holidays = INPUT.lock().unwrap().holidays.clone();
+        let holidays = holidays
+            .map(|path| read_holidays(&path).expect("Could not read holidays."))
+            .unwrap_or_default();
+        let day_types = classify_days(&daily_streams, classification, &holidays);
+        write_groups(group_by_day_type(daily_streams, day_types), builder);
+        return;
+    }
+    if CLUSTERINGARGS.lock().unwrap().per_object {
+        write_groups(group_by_object(daily_streams), builder);
+        return;
+    }
+    let daily_streams = fold_streams(daily_streams);
+    let graph: Box<dyn Writable> = match extend {
+        Some(path) => {
+            let graph = read_graph(&path).expect("Could not read graph to extend.");
+            Box::new(extend_graph(graph, daily_streams))
+        }
+        None => build_graph(daily_streams, builder),
+    };
     graph.to_csv().expect("Could not write output.");
 }
+
+/// Builds a graph for each group of streams and writes it to a subfolder of the output named after the group,
+/// each with statistics of its own. The folder of each group is listed in `groups.csv`.
+fn write_groups(groups: BTreeMap<String, Vec<DailyStream>>, builder: GraphBuilder) {
+    let folders = folder_names(groups.keys().map(String::as_str));
+    let enabled = OUTPUT.lock().unwrap().is_enabled();
+    if enabled {
+        let rows: Vec<String> = groups
+            .keys()
+            .zip(folders.iter())
+            .map(|(name, folder)| format!("{},{}", csv_field(name), folder))
+            .collect();
+        std::fs::write("groups.csv", format!("group,folder\n{}\n", rows.join("\n")))
+            .expect("Could not write groups.");
+    }
+    for ((_, streams), folder) in groups.into_iter().zip(folders) {
+        *STATS.lock().unwrap() = Statistics::default();
+        let graph = build_graph(fold_streams(streams), builder);
+        if enabled {
+            let folder = Path::new(&folder);
+            std::fs::create_dir_all(folder).expect("Could not create group folder.");
+            graph.to_csv_in(folder).expect("Could not write output.");
+        }
+    }
+}
+
+/// If `--weekly` is given, each stream is offset by its weekday.
+fn fold_streams(streams: Vec<DailyStream>) -> Vec<DailyStream> {
+    let weekly = CLUSTERINGARGS.lock().unwrap().weekly;
+    streams
+        .into_iter()
+        .map(|(id, stream)| {
+            if weekly {
+                // Place the stream on its weekday
+                let offset = cyclic_time::weekday_offset(&id.day).unwrap_or_else(|| {
+                    panic!("Can't find the weekday of a stream from {}", id.source)
+                });
+                let stream = stream
+                    .into_iter()
+                    .map(|[x, y, t]| [x, y, t + offset])
+                    .collect();
+                (id, stream)
+            } else {
+                (id, stream)
+            }
+        })
+        .collect()
+}
@@ -0,0 +1,124 @@
+use super::road_network::{Candidate, RoadNetwork};
+use crate::utility::get_distance;
+use crate::CONFIG;
+
+/// Log probability of observing `candidate` given GPS noise with standard deviation `sigma`.
+fn emission(candidate: &Candidate, sigma: f64) -> f64 {
+    -0.5 * (candidate.distance / sigma).powi(2)
+}
+
+/// Log probability of moving between two candidates.
+/// Routes that are much longer than the straight line between the measurements are unlikely.
+fn transition(route_distance: f64, straight_distance: f64, beta: f64) -> f64 {
+    -(route_distance - straight_distance).abs() / beta
+}
+
+/// Matches the trajectory to the network using a hidden Markov model, see
+/// Newson & Krumm, "Hidden Markov map matching through noise and sparseness" (2009).
+///
+/// The states are the [candidates](RoadNetwork::candidates) within `map_matching_radius` of each measurement.
+/// Measurements closer than `2 * map_matching_sigma` to the previous one are skipped, and measurements without candidates
+/// are ignored. If no route connects two consecutive layers, the model is restarted.
+///
+/// Returns the ids of the ways traversed by the most likely route, with consecutive duplicates removed.
+pub fn match_trajectory(network: &RoadNetwork, trj: &[[f64; 3]]) -> Vec<i64> {
+    let sigma = CONFIG.map_matching_sigma;
+    let radius = CONFIG.map_matching_radius;
+
+    let mut layers: Vec<([f64; 2], Vec<Candidate>)> = vec![];
+    for [x, y, _] in trj.iter() {
+        let point = [*x, *y];
+        if let Some((last, _)) = layers.last() {
+            if get_distance(last, &point) < 2.0 * sigma {
+                continue;
+            }
+        }
+        let candidates = network.candidates(&point, radius);
+        if !candidates.is_empty() {
+            layers.push((point, candidates));
+        }
+    }
+    if layers.is_empty() {
+        return vec![];
+    }
+
+    let mut ways: Vec<i64> = vec![];
+    for chain in viterbi(network, &layers) {
+        let mut segments = vec![chain[0].segment];
+        for pair in chain.windows(2) {
+            let straight = get_distance(&pair[0].point, &pair[1].point);
+            let route = network.route(&pair[0], &pair[1], 3.0 * straight + 2.0 * radius);
+            segments.extend(route.into_iter().skip(1));
+        }
+        for sx in segments {
+            let way = network.segments[sx].way;
+            if ways.last() != Some(&way) {
+                ways.push(way);
+            }
+        }
+    }
+    ways
+}
+
+/// Returns the most likely sequences of candidates.
+/// A new sequence is started whenever no route connects two consecutive layers.
+fn viterbi(network: &RoadNetwork, layers: &[([f64; 2], Vec<Candidate>)]) -> Vec<Vec<Candidate>> {
+    let sigma = CONFIG.map_matching_sigma;
+    let beta = CONFIG.map_matching_beta;
+    let radius = CONFIG.map_matching_radius;
+
+    let mut chains = vec![];
+    let mut scores: Vec<f64> = layers[0].1.iter().map(|c| emission(c, sigma)).collect();
+    // Best predecessor of each candidate in the layers of the current chain
+    let mut predecessors: Vec<Vec<usize>> = vec![];
+    let mut start = 0;
+    for i in 1..=layers.len() {
+        let mut next_scores: Vec<f64> = vec![];
+        let mut next_predecessors = vec![];
+        if i < layers.len() {
+            let (prev_point, prev) = &layers[i - 1];
+            let (point, current) = &layers[i];
+            let straight = get_distance(prev_point, point);
+            let max_distance = 3.0 * straight + 2.0 * radius;
+            next_scores = vec![f64::NEG_INFINITY; current.len()];
+            next_predecessors = vec![0; current.len()];
+            for (j, from) in prev.iter().enumerate() {
+                if scores[j] == f64::NEG_INFINITY {
+                    continue;
+                }
+                let routes = network.route_distances(from, current, max_distance);
+                for (k, route) in routes.into_iter().enumerate() {
+                    let score = scores[j]
+                        + transition(route, straight, beta)
+                        + emission(&current[k], sigma);
+                    if score > next_scores[k] {
+                        next_scores[k] = score;
+                        next_predecessors[k] = j;
+                    }
+                }
+            }
+        }
+        if next_scores.iter().all(|s| *s == f64::NEG_INFINITY) {
+            // End of the chain, backtrack from the most likely candidate of the last layer
+            let mut best = (0..scores.len())
+                .max_by(|a, b| scores[*a].partial_cmp(&scores[*b]).unwrap())
+                .unwrap();
+            let mut chain = vec![layers[i - 1].1[best]];
+            for (layer, preds) in (start..(i - 1)).zip(predecessors.iter()).rev() {
+                best = preds[best];
+                chain.push(layers[layer].1[best]);
+            }
+            chain.reverse();
+            chains.push(chain);
+            if i < layers.len() {
+                next_scores = layers[i].1.iter().map(|c| emission(c, sigma)).collect();
+            }
+            predecessors.clear();
+            start = i;
+        } else {
+            predecessors.push(next_predecessors);
+        }
+        scores = next_scores;
+    }
+    chains
+}
@@ -0,0 +1,44 @@
+//! Map matching
+//!
+//! Snaps trajectories to the road and path network of a local OpenStreetMap extract,
+//! s.t. trajectories following the same streets can be recognized even when their GPS traces differ.
+mod matcher;
+mod osm;
+mod road_network;
+pub use matcher::match_trajectory;
+pub use osm::read_network;
+pub use road_network::RoadNetwork;
+
+#[cfg(test)]
+mod test {
+    use super::*;
+    use std::collections::HashMap;
+
+    #[test]
+    fn match_noisy_trajectory_with_turn() {
+        // Way 1 runs east, way 2 runs parallel 60m north of it and way 3 turns south at x=500
+        let nodes: HashMap<i64, [f64; 2]> = vec![
+            (1, [0.0, 0.0]),
+            (2, [500.0, 0.0]),
+            (3, [1000.0, 0.0]),
+            (4, [0.0, 60.0]),
+            (5, [1000.0, 60.0]),
+            (6, [500.0, -500.0]),
+        ]
+        .into_iter()
+        .collect();
+        let ways = vec![(1, vec![1, 2, 3]), (2, vec![4, 5]), (3, vec![2, 6])];
+        let network = RoadNetwork::new(&nodes, &ways);
+
+        let mut trj = vec![];
+        for i in 0..10 {
+            let noise = if i % 2 == 0 { 10.0 } else { -10.0 };
+            trj.push([i as f64 * 50.0, 20.0 + noise, i as f64 * 10000.0]);
+        }
+        for i in 0..10 {
+            let noise = if i % 2 == 0 { 10.0 } else { -10.0 };
+            trj.push([500.0 + noise, -(i as f64) * 50.0, (10 + i) as f64 * 10000.0]);
+        }
+        assert_eq!(match_trajectory(&network, &trj), vec![1, 3]);
+    }
+}
@@ -0,0 +1,498 @@
+use super::RoadNetwork;
+use crate::from_epsg_4326_to_3857;
+use flate2::read::ZlibDecoder;
+use regex::Regex;
+use std::collections::HashMap;
+use std::io::Read;
+use std::path::Path;
+
+/// Tags marking the ways objects can travel along.
+const ROUTABLE: [(&str, Option<&str>); 3] = [
+    ("highway", None),
+    ("railway", None),
+    ("route", Some("ferry")),
+];
+
+fn is_routable(tags: &[(String, String)]) -> bool {
+    tags.iter().any(|(k, v)| {
+        ROUTABLE
+            .iter()
+            .any(|(key, value)| (k == key) & value.is_none_or(|value| v == value))
+    })
+}
+
+fn project(lat: f64, lon: f64) -> [f64; 2] {
+    let [x, y, _] = from_epsg_4326_to_3857(&[lat, lon, 0.0]);
+    [x, y]
+}
+
+/// Reads the road network from an OSM extract.
+///
+/// Files ending in `.pbf` are read as [PBF](parse_osm_pbf), anything else as [XML](parse_osm_xml).
+pub fn read_network(path: &Path) -> RoadNetwork {
+    let is_pbf = path.extension().is_some_and(|ext| ext == "pbf");
+    if is_pbf {
+        let content = std::fs::read(path).expect("Can't read OSM extract");
+        parse_osm_pbf(&content)
+    } else {
+        let content = std::fs::read_to_string(path).expect("Can't read OSM extract");
+        parse_osm_xml(&content)
+    }
+}
+
+/// Parses an OSM XML extract.
+///
+/// Only nodes and [routable](ROUTABLE) ways are used, relations are ignored.
+pub fn parse_osm_xml(xml: &str) -> RoadNetwork {
+    let attr = Regex::new(r#"([\w:]+)=["']([^"']*)["']"#).unwrap();
+    let node = Regex::new(r"<node\s([^>]*)>").unwrap();
+    let way = Regex::new(r"(?s)<way\s([^>]*)>(.*?)</way>").unwrap();
+    let nd = Regex::new(r#"<nd\s+ref=["'](-?\d+)["']"#).unwrap();
+    let tag = Regex::new(r#"<tag\s+k=["']([^"']*)["']\s+v=["']([^"']*)["']"#).unwrap();
+    let attributes = |s: &str| -> HashMap<String, String> {
+        attr.captures_iter(s)
+            .map(|cap| (cap[1].to_string(), cap[2].to_string()))
+            .collect()
+    };
+
+    let mut nodes = HashMap::new();
+    for cap in node.captures_iter(xml) {
+        let attrs = attributes(&cap[1]);
+        if let (Some(id), Some(lat), Some(lon)) =
+            (attrs.get("id"), attrs.get("lat"), attrs.get("lon"))
+        {
+            if let (Ok(id), Ok(lat), Ok(lon)) = (id.parse(), lat.parse(), lon.parse()) {
+                nodes.insert(id, project(lat, lon));
+            }
+        }
+    }
+    let mut ways = vec![];
+    for cap in way.captures_iter(xml) {
+        let tags: Vec<(String, String)> = tag
+            .captures_iter(&cap[2])
+            .map(|t| (t[1].to_string(), t[2].to_string()))
+            .collect();
+        if !is_routable(&tags) {
+            continue;
+        }
+        if let Some(Ok(id)) = attributes(&cap[1]).get("id").map(|id| id.parse::<i64>()) {
+            let refs = nd
+                .captures_iter(&cap[2])
+                .filter_map(|r| r[1].parse::<i64>().ok())
+                .collect();
+            ways.push((id, refs));
+        }
+    }
+    RoadNetwork::new(&nodes, &ways)
+}
+
+/// A field of a protocol buffer message.
+enum Field<'a> {
+    Varint(u64),
+    Bytes(&'a [u8]),
+    Fixed,
+}
+
+/// Minimal reader of protocol buffer messages, sufficient for the OSM PBF format.
+struct Message<'a> {
+    buf: &'a [u8],
+    pos: usize,
+}
+
+impl<'a> Message<'a> {
+    fn new(buf: &'a [u8]) -> Self {
+        Message { buf, pos: 0 }
+    }
+
+    fn varint(&mut self) -> u64 {
+        let mut value = 0;
+        let mut shift = 0;
+        while self.pos < self.buf.len() {
+            let byte = self.buf[self.pos];
+            self.pos += 1;
+            value |= ((byte & 0x7f) as u64) << shift;
+            if byte & 0x80 == 0 {
+                break;
+            }
+            shift += 7;
+        }
+        value
+    }
+
+    fn bytes(&mut self, len: usize) -> &'a [u8] {
+        let end = (self.pos + len).min(self.buf.len());
+        let bytes = &self.buf[self.pos..end];
+        self.pos = end;
+        bytes
+    }
+}
+
+impl<'a> Iterator for Message<'a> {
+    type Item = (u64, Field<'a>);
+
+    fn next(&mut self) -> Option<Self::Item> {
+        if self.pos >= self.buf.len() {
+            return None;
+        }
+        let key = self.varint();
+        let field = match key & 7 {
+            0 => Field::Varint(self.varint()),
+            1 => {
+                self.bytes(8);
+                Field::Fixed
+            }
+            2 => {
+                let len = self.varint() as usize;
+                Field::Bytes(self.bytes(len))
+            }
+            5 => {
+                self.bytes(4);
+                Field::Fixed
+            }
+            _ => return None,
+        };
+        Some((key >> 3, field))
+    }
+}
+
+fn zigzag(v: u64) -> i64 {
+    ((v >> 1) as i64) ^ -((v & 1) as i64)
+}
+
+fn packed(bytes: &[u8]) -> Vec<u64> {
+    let mut msg = Message::new(bytes);
+    let mut values = vec![];
+    while msg.pos < bytes.len() {
+        values.push(msg.varint());
+    }
+    values
+}
+
+/// Decodes packed, delta coded signed integers.
+fn packed_delta(bytes: &[u8]) -> Vec<i64> {
+    packed(bytes)
+        .into_iter()
+        .scan(0, |acc, v| {
+            *acc += zigzag(v);
+            Some(*acc)
+        })
+        .collect()
+}
+
+/// Parses an OSM PBF extract.
+///
+/// Supports raw and zlib compressed blobs. Only nodes and [routable](ROUTABLE) ways are used.
+pub fn parse_osm_pbf(pbf: &[u8]) -> RoadNetwork {
+    let mut nodes = HashMap::new();
+    let mut ways = vec![];
+    let mut pos = 0;
+    while pos + 4 <= pbf.len() {
+        let header_len =
+            u32::from_be_bytes([pbf[pos], pbf[pos + 1], pbf[pos + 2], pbf[pos + 3]]) as usize;
+        pos += 4;
+        let mut blob_type = "";
+        let mut blob_len = 0;
+        for (field, value) in Message::new(&pbf[pos..(pos + header_len).min(pbf.len())]) {
+            match (field, value) {
+                (1, Field::Bytes(b)) => blob_type = std::str::from_utf8(b).unwrap_or_default(),
+                (3, Field::Varint(v)) => blob_len = v as usize,
+                _ => (),
+            }
+        }
+        pos += header_len;
+        let blob = &pbf[pos.min(pbf.len())..(pos + blob_len).min(pbf.len())];
+        pos += blob_len;
+        if blob_type == "OSMData" {
+            let data = decode_blob(blob);
+            parse_primitive_block(&data, &mut nodes, &mut ways);
+        }
+    }
+    RoadNetwork::new(&nodes, &ways)
+}
+
+fn decode_blob(blob: &[u8]) -> Vec<u8> {
+    let mut data = vec![];
+    for (field, value) in Message::new(blob) {
+        match (field, value) {
+            (1, Field::Bytes(raw)) => data = raw.to_vec(),
+            (3, Field::Bytes(zlib)) => {
+                ZlibDecoder::new(zlib)
+                    .read_to_end(&mut data)
+                    .expect("Corrupt OSM PBF blob");
+            }
+            (4..=7, Field::Bytes(_)) => panic!("Only zlib compressed OSM PBF files are supported"),
+            _ => (),
+        }
+    }
+    data
+}
+
+fn parse_primitive_block(
+    block: &[u8],
+    nodes: &mut HashMap<i64, [f64; 2]>,
+    ways: &mut Vec<(i64, Vec<i64>)>,
+) {
+    let mut strings: Vec<String> = vec![];
+    let mut groups = vec![];
+    let mut granularity = 100.0;
+    let (mut lat_offset, mut lon_offset) = (0.0, 0.0);
+    for (field, value) in Message::new(block) {
+        match (field, value) {
+            (1, Field::Bytes(table)) => {
+                strings = Message::new(table)
+                    .filter_map(|(f, v)| match (f, v) {
+                        (1, Field::Bytes(s)) => Some(String::from_utf8_lossy(s).to_string()),
+                        _ => None,
+                    })
+                    .collect()
+            }
+            (2, Field::Bytes(group)) => groups.push(group),
+            (17, Field::Varint(v)) => granularity = v as f64,
+            (19, Field::Varint(v)) => lat_offset = v as i64 as f64,
+            (20, Field::Varint(v)) => lon_offset = v as i64 as f64,
+            _ => (),
+        }
+    }
+    let coord = |lat: i64, lon: i64| {
+        project(
+            1e-9 * (lat_offset + granularity * lat as f64),
+            1e-9 * (lon_offset + granularity * lon as f64),
+        )
+    };
+    for group in groups {
+        for (field, value) in Message::new(group) {
+            match (field, value) {
+                (1, Field::Bytes(node)) => {
+                    let (mut id, mut lat, mut lon) = (0, 0, 0);
+                    for (f, v) in Message::new(node) {
+                        match (f, v) {
+                            (1, Field::Varint(v)) => id = zigzag(v),
+                            (8, Field::Varint(v)) => lat = zigzag(v),
+                            (9, Field::Varint(v)) => lon = zigzag(v),
+                            _ => (),
+                        }
+                    }
+                    nodes.insert(id, coord(lat, lon));
+                }
+                (2, Field::Bytes(dense)) => {
+                    let (mut ids, mut lats, mut lons) = (vec![], vec![], vec![]);
+                    for (f, v) in Message::new(dense) {
+                        match (f, v) {
+                            (1, Field::Bytes(b)) => ids = packed_delta(b),
+                            (8, Field::Bytes(b)) => lats = packed_delta(b),
+                            (9, Field::Bytes(b)) => lons = packed_delta(b),
+                            _ => (),
+                        }
+                    }
+                    for ((id, lat), lon) in ids.into_iter().zip(lats).zip(lons) {
+                        nodes.insert(id, coord(lat, lon));
+                    }
+                }
+                (3, Field::Bytes(way)) => {
+                    let (mut id, mut keys, mut vals, mut refs) = (0, vec![], vec![], vec![]);
+                    for (f, v) in Message::new(way) {
+                        match (f, v) {
+                            (1, Field::Varint(v)) => id = v as i64,
+                            (2, Field::Bytes(b)) => keys = packed(b),
+                            (3, Field::Bytes(b)) => vals = packed(b),
+                            (8, Field::Bytes(b)) => refs = packed_delta(b),
+                            _ => (),
+                        }
+                    }
+                    let string = |i: u64| strings.get(i as usize).cloned().unwrap_or_default();
+                    let tags: Vec<(String, String)> = keys
+                        .into_iter()
+                        .zip(vals)
+                        .map(|(k, v)| (string(k), string(v)))
+                        .collect();
+                    if is_routable(&tags) {
+                        ways.push((id, refs));
+                    }
+                }
+                _ => (),
+            }
+        }
+    }
+}
+
+#[cfg(test)]
+mod test {
+    use super::*;
+    use flate2::write::ZlibEncoder;
+    use flate2::Compression;
+    use std::io::Write;
+
+    fn encode_varint(mut v: u64) -> Vec<u8> {
+        let mut bytes = vec![];
+        while v >= 0x80 {
+            bytes.push((v as u8 & 0x7f) | 0x80);
+            v >>= 7;
+        }
+        bytes.push(v as u8);
+        bytes
+    }
+
+    fn varint_field(field: u64, v: u64) -> Vec<u8> {
+        [encode_varint(field << 3), encode_varint(v)].concat()
+    }
+
+    fn bytes_field(field: u64, bytes: &[u8]) -> Vec<u8> {
+        [
+            encode_varint((field << 3) | 2),
+            encode_varint(bytes.len() as u64),
+            bytes.to_vec(),
+        ]
+        .concat()
+    }
+
+    fn encode_zigzag(v: i64) -> u64 {
+        ((v << 1) ^ (v >> 63)) as u64
+    }
+
+    fn packed_field(field: u64, values: &[u64]) -> Vec<u8> {
+        let bytes: Vec<u8> = values.iter().flat_map(|v| encode_varint(*v)).collect();
+        bytes_field(field, &bytes)
+    }
+
+    /// Packs the values delta coded as signed integers.
+    fn delta_field(field: u64, values: &[i64]) -> Vec<u8> {
+        let deltas: Vec<u64> = values
+            .iter()
+            .scan(0, |prev, v| {
+                let delta = v - *prev;
+                *prev = *v;
+                Some(encode_zigzag(delta))
+            })
+            .collect();
+        packed_field(field, &deltas)
+    }
+
+    /// Frames the blob with its header, compressing the data if `zlib` is set.
+    fn file_block(blob_type: &str, data: &[u8], zlib: bool) -> Vec<u8> {
+        let blob = if zlib {
+            let mut encoder = ZlibEncoder::new(vec![], Compression::default());
+            encoder.write_all(data).unwrap();
+            [
+                varint_field(2, data.len() as u64),
+                bytes_field(3, &encoder.finish().unwrap()),
+            ]
+            .concat()
+        } else {
+            bytes_field(1, data)
+        };
+        let header = [
+            bytes_field(1, blob_type.as_bytes()),
+            varint_field(3, blob.len() as u64),
+        ]
+        .concat();
+        [(header.len() as u32).to_be_bytes().to_vec(), header, blob].concat()
+    }
+
+    #[test]
+    fn parse_pbf_extract() {
+        // Coordinates are stored in units of `granularity` nanodegrees relative to the offsets
+        let (granularity, lat_offset, lon_offset) = (1000, 1_000_000, 2_000_000);
+        let units =
+            |degrees: f64, offset: i64| (degrees * 1e9) as i64 / granularity - offset / granularity;
+        let coords = [(55.0, 10.0), (55.0, 10.01), (55.01, 10.01), (55.02, 10.02)];
+        let strings: Vec<u8> = ["", "highway", "residential", "building", "yes"]
+            .iter()
+            .flat_map(|s| bytes_field(1, s.as_bytes()))
+            .collect();
+        let dense = [
+            delta_field(1, &[1, 2, 3]),
+            delta_field(
+                8,
+                &coords[..3]
+                    .iter()
+                    .map(|c| units(c.0, lat_offset))
+                    .collect::<Vec<_>>(),
+            ),
+            delta_field(
+                9,
+                &coords[..3]
+                    .iter()
+                    .map(|c| units(c.1, lon_offset))
+                    .collect::<Vec<_>>(),
+            ),
+        ]
+        .concat();
+        let node = [
+            varint_field(1, encode_zigzag(4)),
+            varint_field(8, encode_zigzag(units(coords[3].0, lat_offset))),
+            varint_field(9, encode_zigzag(units(coords[3].1, lon_offset))),
+        ]
+        .concat();
+        let road = [
+            varint_field(1, 10),
+            packed_field(2, &[1]),
+            packed_field(3, &[2]),
+            delta_field(8, &[1, 2, 3, 4]),
+        ]
+        .concat();
+        let building = [
+            varint_field(1, 11),
+            packed_field(2, &[3]),
+            packed_field(3, &[4]),
+            delta_field(8, &[1, 3]),
+        ]
+        .concat();
+        let block = [
+            bytes_field(1, &strings),
+            bytes_field(2, &bytes_field(2, &dense)),
+            bytes_field(
+                2,
+                &[
+                    bytes_field(1, &node),
+                    bytes_field(3, &road),
+                    bytes_field(3, &building),
+                ]
+                .concat(),
+            ),
+            varint_field(17, granularity as u64),
+            varint_field(19, lat_offset as u64),
+            varint_field(20, lon_offset as u64),
+        ]
+        .concat();
+        let pbf = [
+            file_block("OSMHeader", &[], false),
+            file_block("OSMData", &block, true),
+        ]
+        .concat();
+
+        let network = parse_osm_pbf(&pbf);
+        assert_eq!(network.nodes.len(), 4);
+        for (node, (lat, lon)) in network.nodes.iter().zip(coords) {
+            let [x, y] = project(lat, lon);
+            assert!((node[0] - x).abs() < 1e-3, "{:?} != {:?}", node, [x, y]);
+            assert!((node[1] - y).abs() < 1e-3, "{:?} != {:?}", node, [x, y]);
+        }
+        assert_eq!(network.segments.len(), 3);
+        assert!(network.segments.iter().all(|segment| segment.way == 10));
+    }
+
+    #[test]
+    fn parse_xml_extract() {
+        let xml = r#"<?xml version="1.0" encoding="UTF-8"?>
+<osm version="0.6">
+  <node id="1" lat="55.0" lon="10.0"/>
+  <node id="2" lat="55.0" lon="10.01"/>
+  <node id="3" lat="55.01" lon="10.01"/>
+  <way id="10">
+    <nd ref="1"/>
+    <nd ref="2"/>
+    <nd ref="3"/>
+    <tag k="highway" v="residential"/>
+  </way>
+  <way id="11">
+    <nd ref="1"/>
+    <nd ref="3"/>
+    <tag k="building" v="yes"/>
+  </way>
+</osm>"#;
+        let network = parse_osm_xml(xml);
+        assert_eq!(network.nodes.len(), 3);
+        assert_eq!(network.segments.len(), 2);
+        assert!(network.segments.iter().all(|segment| segment.way == 10));
+    }
+}
@@ -0,0 +1,257 @@
+use crate::utility::get_distance;
+use std::cmp::Ordering;
+use std::collections::{BinaryHeap, HashMap, HashSet};
+
+/// Side length in meters of the grid cells used to look up segments near a point.
+const CELL_SIZE: f64 = 250.0;
+
+/// A straight piece of an OSM way between two consecutive nodes.
+#[derive(Clone, Copy, Debug)]
+pub struct Segment {
+    /// Id of the OSM way the segment belongs to.
+    pub way: i64,
+    /// Index of the first node.
+    pub a: usize,
+    /// Index of the second node.
+    pub b: usize,
+    pub length: f64,
+}
+
+/// A point on the network that a measurement may be snapped to.
+#[derive(Clone, Copy, Debug)]
+pub struct Candidate {
+    /// Index of the segment the candidate lies on.
+    pub segment: usize,
+    /// The projection of the measurement onto the segment in EPSG 3857.
+    pub point: [f64; 2],
+    /// Distance from the first node of the segment to `point`.
+    pub offset: f64,
+    /// Distance from the measurement to `point`.
+    pub distance: f64,
+}
+
+/// The road and path network of an OSM extract.
+///
+/// Ways are treated as undirected, since the objects we track are not necessarily bound by one-way streets.
+#[derive(Clone, Debug, Default)]
+pub struct RoadNetwork {
+    /// EPSG 3857 coordinates of the nodes.
+    pub nodes: Vec<[f64; 2]>,
+    pub segments: Vec<Segment>,
+    /// Segments incident to each node along with the node in the other end.
+    adjacency: Vec<Vec<(usize, usize)>>,
+    /// Segments overlapping each grid cell.
+    grid: HashMap<(i64, i64), Vec<usize>>,
+}
+
+/// Entry of the priority queue used when routing.
+#[derive(PartialEq)]
+struct Visit {
+    cost: f64,
+    node: usize,
+}
+
+impl Eq for Visit {}
+
+impl Ord for Visit {
+    fn cmp(&self, other: &Self) -> Ordering {
+        // Reversed s.t. the heap pops the cheapest visit
+        other
+            .cost
+            .partial_cmp(&self.cost)
+            .unwrap_or(Ordering::Equal)
+    }
+}
+
+impl PartialOrd for Visit {
+    fn partial_cmp(&self, other: &Self) -> Option<Ordering> {
+        Some(self.cmp(other))
+    }
+}
+
+/// Shortest distances from the start of a route, along with the segment used to reach each node.
+type Routes = HashMap<usize, (f64, Option<(usize, usize)>)>;
+
+impl RoadNetwork {
+    /// Constructs the network from EPSG 3857 node coordinates keyed by OSM id and ways given as `(id, node ids)`.
+    /// Nodes that are referenced but missing from `nodes` are skipped.
+    pub fn new(nodes: &HashMap<i64, [f64; 2]>, ways: &[(i64, Vec<i64>)]) -> Self {
+        let mut network = RoadNetwork::default();
+        let mut index: HashMap<i64, usize> = HashMap::new();
+        for (way, refs) in ways {
+            let refs: Vec<usize> = refs
+                .iter()
+                .filter_map(|id| {
+                    let coord = nodes.get(id)?;
+                    Some(*index.entry(*id).or_insert_with(|| {
+                        network.nodes.push(*coord);
+                        network.adjacency.push(vec![]);
+                        network.nodes.len() - 1
+                    }))
+                })
+                .collect();
+            for pair in refs.windows(2) {
+                let (a, b) = (pair[0], pair[1]);
+                if a == b {
+                    continue;
+                }
+                let length = get_distance(&network.nodes[a], &network.nodes[b]);
+                network.segments.push(Segment {
+                    way: *way,
+                    a,
+                    b,
+                    length,
+                });
+                let sx = network.segments.len() - 1;
+                network.adjacency[a].push((sx, b));
+                network.adjacency[b].push((sx, a));
+                for cell in network.cells(&network.nodes[a], &network.nodes[b], 0.0) {
+                    network.grid.entry(cell).or_insert_with(Vec::new).push(sx);
+                }
+            }
+        }
+        network
+    }
+
+    /// Returns the grid cells covered by the bounding box of `p` and `q` expanded by `margin`.
+    fn cells(&self, p: &[f64; 2], q: &[f64; 2], margin: f64) -> Vec<(i64, i64)> {
+        let cell = |v: f64| (v / CELL_SIZE).floor() as i64;
+        let (x1, x2) = (cell(p[0].min(q[0]) - margin), cell(p[0].max(q[0]) + margin));
+        let (y1, y2) = (cell(p[1].min(q[1]) - margin), cell(p[1].max(q[1]) + margin));
+        (x1..=x2)
+            .flat_map(|x| (y1..=y2).map(move |y| (x, y)))
+            .collect()
+    }
+
+    /// Returns the projections of `point` onto segments within `radius`, nearest first.
+    /// Only the nearest projection on each way is kept.
+    pub fn candidates(&self, point: &[f64; 2], radius: f64) -> Vec<Candidate> {
+        let segments: HashSet<usize> = self
+            .cells(point, point, radius)
+            .iter()
+            .filter_map(|cell| self.grid.get(cell))
+            .flatten()
+            .copied()
+            .collect();
+        let mut candidates: Vec<Candidate> = segments
+            .into_iter()
+            .map(|sx| self.project(sx, point))
+            .filter(|candidate| candidate.distance <= radius)
+            .collect();
+        candidates.sort_by(|a, b| a.distance.partial_cmp(&b.distance).unwrap());
+        let mut ways = HashSet::new();
+        candidates.retain(|candidate| ways.insert(self.segments[candidate.segment].way));
+        candidates
+    }
+
+    /// Projects `point` onto the segment with index `sx`.
+    fn project(&self, sx: usize, point: &[f64; 2]) -> Candidate {
+        let segment = &self.segments[sx];
+        let (a, b) = (self.nodes[segment.a], self.nodes[segment.b]);
+        let (dx, dy) = (b[0] - a[0], b[1] - a[1]);
+        // Distinct nodes may share a position, making the segment a point
+        let t = if segment.length > 0.0 {
+            (((point[0] - a[0]) * dx + (point[1] - a[1]) * dy) / (dx * dx + dy * dy))
+                .clamp(0.0, 1.0)
+        } else {
+            0.0
+        };
+        let projection = [a[0] + t * dx, a[1] + t * dy];
+        Candidate {
+            segment: sx,
+            point: projection,
+            offset: t * segment.length,
+            distance: get_distance(point, &projection),
+        }
+    }
+
+    /// Runs Dijkstra from the candidate, visiting nodes no further away than `max_distance`.
+    fn routes_from(&self, from: &Candidate, max_distance: f64) -> Routes {
+        let segment = &self.segments[from.segment];
+        let mut routes: Routes = HashMap::new();
+        let mut heap = BinaryHeap::new();
+        for (node, cost) in [
+            (segment.a, from.offset),
+            (segment.b, segment.length - from.offset),
+        ] {
+            routes.insert(node, (cost, None));
+            heap.push(Visit { cost, node });
+        }
+        while let Some(Visit { cost, node }) = heap.pop() {
+            if cost > routes[&node].0 {
+                continue;
+            }
+            for (sx, next) in self.adjacency[node].iter() {
+                let next_cost = cost + self.segments[*sx].length;
+                if next_cost > max_distance {
+                    continue;
+                }
+                let known = routes.get(next).map(|(c, _)| *c).unwrap_or(f64::INFINITY);
+                if next_cost < known {
+                    routes.insert(*next, (next_cost, Some((*sx, node))));
+                    heap.push(Visit {
+                        cost: next_cost,
+                        node: *next,
+                    });
+                }
+            }
+        }
+        routes
+    }
+
+    /// Returns the distance and the node used to enter the segment of `to`, given the routes from another candidate.
+    fn route_end(&self, routes: &Routes, to: &Candidate) -> Option<(f64, usize)> {
+        let segment = &self.segments[to.segment];
+        let via_a = routes
+            .get(&segment.a)
+            .map(|(c, _)| (c + to.offset, segment.a));
+        let via_b = routes
+            .get(&segment.b)
+            .map(|(c, _)| (c + segment.length - to.offset, segment.b));
+        match (via_a, via_b) {
+            (Some(a), Some(b)) => Some(if a.0 <= b.0 { a } else { b }),
+            (a, b) => a.or(b),
+        }
+    }
+
+    /// Returns the length of the shortest route from `from` to each of `to`.
+    /// The length is infinite if the route is longer than `max_distance`.
+    pub fn route_distances(
+        &self,
+        from: &Candidate,
+        to: &[Candidate],
+        max_distance: f64,
+    ) -> Vec<f64> {
+        let routes = self.routes_from(from, max_distance);
+        to.iter()
+            .map(|candidate| {
+                if candidate.segment == from.segment {
+                    (candidate.offset - from.offset).abs()
+                } else {
+                    self.route_end(&routes, candidate)
+                        .map(|(distance, _)| distance)
+                        .filter(|distance| *distance <= max_distance)
+                        .unwrap_or(f64::INFINITY)
+                }
+            })
+            .collect()
+    }
+
+    /// Returns the segments traversed on the shortest route from `from` to `to`, including their own segments.
+    pub fn route(&self, from: &Candidate, to: &Candidate, max_distance: f64) -> Vec<usize> {
+        if from.segment == to.segment {
+            return vec![from.segment];
+        }
+        let routes = self.routes_from(from, max_distance);
+        let mut segments = vec![to.segment];
+        if let Some((_, mut node)) = self.route_end(&routes, to) {
+            while let Some((_, Some((sx, prev)))) = routes.get(&node) {
+                segments.push(*sx);
+                node = *prev;
+            }
+        }
+        segments.push(from.segment);
+        segments.reverse();
+        segments
+    }
+}
@@ -1,17 +1,97 @@
-use chrono::{Datelike, NaiveDate, NaiveDateTime, Timelike};
+use chrono::{NaiveDate, NaiveDateTime, Timelike};
 use regex::Regex;
+use serde::{Deserialize, Serialize};
+use std::collections::{BTreeMap, HashMap};
+
+/// Identifies the origin of a daily stream.
+#[derive(Clone, Debug, Default, PartialEq, Eq, Hash, PartialOrd, Ord, Serialize, Deserialize)]
+pub struct StreamId {
+    /// The file the stream was read from, or `stdin`.
+    pub source: String,
+    /// The moving object, e.g. the MMSI number of a vessel. Empty if the format doesn't identify objects.
+    pub object: String,
+    /// The date of the stream formatted as `YYYY-MM-DD`.
+    pub day: String,
+}
+
+/// The points of a stream along with its origin.
+pub type DailyStream = (StreamId, Vec<[f64; 3]>);
+
+impl StreamId {
+    /// Returns the name of the moving object, or the name of the source file if the format doesn't identify objects.
+    pub fn object_name(&self) -> String {
+        if self.object.is_empty() {
+            std::path::Path::new(&self.source)
+                .file_stem()
+                .map(|stem| stem.to_string_lossy().to_string())
+                .unwrap_or_else(|| self.source.clone())
+        } else {
+            self.object.clone()
+        }
+    }
+}
+
+/// Groups the streams by their [object](StreamId::object_name), keeping the order of the streams of each object.
+pub fn group_by_object(streams: Vec<DailyStream>) -> BTreeMap<String, Vec<DailyStream>> {
+    let mut groups: BTreeMap<String, Vec<DailyStream>> = BTreeMap::new();
+    for (id, stream) in streams {
+        groups
+            .entry(id.object_name())
+            .or_default()
+            .push((id, stream));
+    }
+    groups
+}
+
+/// Identifies a trajectory by its stream and its index among the trajectories the stream is segmented into.
+#[derive(Clone, Debug, Default, PartialEq, Eq, Hash, PartialOrd, Ord, Serialize, Deserialize)]
+pub struct TrajectoryId {
+    pub stream: StreamId,
+    pub segment: usize,
+}
+
+/// Collects points into a stream for each object and day.
+///
+/// Streams are kept in the order they first appear in the input.
+#[derive(Default)]
+struct StreamCollector {
+    streams: Vec<(StreamId, Vec<[f64; 3]>)>,
+    index: HashMap<(String, String), usize>,
+}
+
+impl StreamCollector {
+    fn push(&mut self, object: &str, day: &str, point: [f64; 3]) {
+        let key = (object.to_string(), day.to_string());
+        let idx = match self.index.get(&key) {
+            Some(idx) => *idx,
+            None => {
+                let id = StreamId {
+                    source: String::new(),
+                    object: key.0.clone(),
+                    day: key.1.clone(),
+                };
+                self.streams.push((id, vec![]));
+                self.index.insert(key, self.streams.len() - 1);
+                self.streams.len() - 1
+            }
+        };
+        self.streams[idx].1.push(point);
+    }
+
+    fn into_streams(self) -> Vec<(StreamId, Vec<[f64; 3]>)> {
+        self.streams
+    }
+}
 
 /// Parses a string containing GPX data.
 ///
 /// Creates an array with EPSG 3857 projected `[easting, northing, time]` for each `<trkpt>`.
 /// The date part of `time` is
 /// stripped and the timestamp is converted to milliseconds.
-pub fn parse_gpx(gpx: String) -> Vec<Vec<[f64; 3]>> {
+pub fn parse_gpx(gpx: String) -> Vec<(StreamId, Vec<[f64; 3]>)> {
     println!("Parsing GPX");
-    let mut trjs: Vec<Vec<[f64; 3]>> = Vec::new();
-    let mut trj: Vec<[f64; 3]> = Vec::new();
+    let mut collector = StreamCollector::default();
     let re = Regex::new(r"lat=\W(\d+[[:punct:]]\d+)\W\slon=\W(\d+[[:punct:]]\d+)\W{2}[[:space:]]*<ele>\d+[[:punct:]]\d+</ele>[[:space:]]*<time>(\d{4})-(\d{2})-(\d{2})T(\d{2}):(\d{2}):(\d{2})[[:punct:]](\d+)").unwrap();
-    let (mut yr_, mut mn_, mut da_): (i32, u32, u32) = (0, 0, 0);
     for cap in re.captures_iter(&gpx) {
         let lat: f64 = cap[1].parse::<f64>().unwrap();
         let lon: f64 = cap[2].parse::<f64>().unwrap();
@@ -25,39 +105,27 @@ pub fn parse_gpx(gpx: String) -> Vec<Vec<[f64; 3]>> {
         let time = NaiveDate::from_ymd(1970, 1, 1)
             .and_hms_milli(h, m, s, ms)
             .timestamp_millis() as f64;
-        if yr != yr_ || mn != mn_ || da != da_ {
-            yr_ = yr;
-            mn_ = mn;
-            da_ = da;
-            if !trj.is_empty() {
-                trjs.push(trj);
-                trj = vec![];
-            }
-        }
+        let day = format!("{:04}-{:02}-{:02}", yr, mn, da);
         let c = crate::from_epsg_4326_to_3857(&[lat, lon, time]);
-        trj.push(c);
-    }
-    if !trj.is_empty() {
-        trjs.push(trj);
+        collector.push("", &day, c);
     }
-    trjs
+    collector.into_streams()
 }
 
 /// Parses a string containing PLT data.
 ///
 /// Specifically designed to data from Geolife Trajectories 1.3.
 /// Creates an array with EPSG 3857 `[easting, northing, time]` coordinates and time.
-/// We only use fields 1 (latitude), 2(longitude), 5(days) and 7(time).
+/// We only use fields 1 (latitude), 2(longitude), 6(date) and 7(time).
 /// Each day is put in a separate trajectory(`Vec`)
-pub fn parse_plt(plt: String) -> Vec<Vec<[f64; 3]>> {
+pub fn parse_plt(plt: String) -> Vec<(StreamId, Vec<[f64; 3]>)> {
     println!("Parsing PLT");
-    let mut trjs: Vec<Vec<[f64; 3]>> = Vec::new();
-    let mut trj: Vec<[f64; 3]> = Vec::new();
-    let mut last_day = -1;
+    let mut collector = StreamCollector::default();
     let lines = plt.lines();
     for line in lines {
         let mut fields = line.split(',');
         let mut coord: [f64; 3] = [f64::NAN, f64::NAN, f64::NAN];
+        let mut day = "";
 
         if let Some(lat) = fields.next() {
             if let Ok(lat) = lat.parse::<f64>() {
@@ -69,18 +137,10 @@ pub fn parse_plt(plt: String) -> Vec<Vec<[f64; 3]>> {
                 coord[1] = lon;
             }
         };
-        if let Some(day) = fields.nth(2) {
-            if let Ok(day) = day.parse::<f64>() {
-                if day as i32 != last_day {
-                    last_day = day as i32;
-                    if !trj.is_empty() {
-                        trjs.push(trj);
-                        trj = vec![];
-                    }
-                }
-            }
+        if let Some(date) = fields.nth(3) {
+            day = date;
         }
-        if let Some(time) = fields.nth(1) {
+        if let Some(time) = fields.next() {
             let mut time = time.split(':');
             let ms: f64 = match [time.next(), time.next(), time.next()] {
                 [Some(h), Some(m), Some(s)] => {
@@ -97,41 +157,31 @@ pub fn parse_plt(plt: String) -> Vec<Vec<[f64; 3]>> {
         }
         if coord[0].is_finite() & coord[1].is_finite() & coord[2].is_finite() {
             let coord = crate::from_epsg_4326_to_3857(&coord);
-            trj.push(coord);
+            collector.push("", day, coord);
         }
     }
-    if !trj.is_empty() {
-        trjs.push(trj);
-    }
-    trjs
+    collector.into_streams()
 }
 
 /// Parses a string containing AIS data.
 ///
 /// Specifically designed to the AIS Brest 2009 [dataset](https://chorochronos.datastories.org/).
 /// Creates an array with EPSG 3857 `[easting, northing, time]` coordinates and time.
-/// We only use fields 3 (latitude), 4(longitude), and 2(time).
-/// Each day is put in a separate trajectory(`Vec`)
-pub fn parse_ais(content: String) -> Vec<Vec<[f64; 3]>> {
+/// We only use fields 1 (MMSI number), 3 (latitude), 4(longitude), and 2(time).
+/// Each day of each vessel is put in a separate trajectory(`Vec`)
+pub fn parse_ais(content: String) -> Vec<(StreamId, Vec<[f64; 3]>)> {
     println!("Parsing AIS");
-    let mut trjs: Vec<Vec<[f64; 3]>> = Vec::new();
-    let mut trj: Vec<[f64; 3]> = Vec::new();
-    let mut last_day = -1;
+    let mut collector = StreamCollector::default();
     let lines = content.lines();
     for line in lines {
         let mut fields = line.split(',');
         let mut coord: [f64; 3] = [f64::NAN, f64::NAN, f64::NAN];
+        let mut day = String::new();
 
-        if let Some(time) = fields.nth(1) {
+        let mmsi = fields.next().unwrap_or_default();
+        if let Some(time) = fields.next() {
             if let Ok(time) = NaiveDateTime::parse_from_str(time, "%Y-%m-%d %H:%M:%S") {
-                let day = time.num_days_from_ce();
-                if day != last_day {
-                    last_day = day;
-                    if !trj.is_empty() {
-                        trjs.push(trj);
-                        trj = vec![];
-                    }
-                }
+                day = time.date().to_string();
                 coord[2] = (time.num_seconds_from_midnight() as f64) * 1000.0;
             }
         };
@@ -149,38 +199,27 @@ pub fn parse_ais(content: String) -> Vec<Vec<[f64; 3]>> {
 
         if coord[0].is_finite() & coord[1].is_finite() & coord[2].is_finite() {
             let coord = crate::from_epsg_4326_to_3857(&coord);
-            trj.push(coord);
+            collector.push(mmsi, &day, coord);
         }
     }
-    if !trj.is_empty() {
-        trjs.push(trj);
-    }
-    trjs
+    collector.into_streams()
 }
 
 /// Parses synthetic data from [https://github.com/NicklasXYZ/rtdm]
 /// The original data is located in `RealtimeTrajectoryDataMining/rtdm/scripts/data`
 /// It is originally in json, but we have extracted trajectories into separate csv-files before parsing.
-pub fn parse_synthetic(content: String) -> Vec<Vec<[f64; 3]>> {
+pub fn parse_synthetic(content: String) -> Vec<(StreamId, Vec<[f64; 3]>)> {
     //println!("Parsing Synthetic Data");
-    let mut trjs: Vec<Vec<[f64; 3]>> = Vec::new();
-    let mut trj: Vec<[f64; 3]> = Vec::new();
-    let mut last_day = -1;
+    let mut collector = StreamCollector::default();
     let lines = content.lines();
     for line in lines {
         let mut fields = line.split(',');
         let mut coord: [f64; 3] = [f64::NAN, f64::NAN, f64::NAN];
+        let mut day = String::new();
 
         if let Some(time) = fields.next() {
             if let Ok(time) = NaiveDateTime::parse_from_str(time, "%Y-%m-%d %H:%M:%S%.f") {
-                let day = time.num_days_from_ce();
-                if day != last_day {
-                    last_day = day;
-                    if !trj.is_empty() {
-                        trjs.push(trj);
-                        trj = vec![];
-                    }
-                }
+                day = time.date().to_string();
                 coord[2] = (time.num_seconds_from_midnight() as f64) * 1000.0
                     + (time.timestamp_subsec_millis() as f64);
             }
@@ -196,15 +235,13 @@ pub fn parse_synthetic(content: String) -> Vec<Vec<[f64; 3]>> {
                 coord[1] = lon;
             }
         };
+        let uid = fields.next().unwrap_or_default();
         if coord[0].is_finite() & coord[1].is_finite() & coord[2].is_finite() {
             let coord = crate::from_epsg_4326_to_3857(&coord);
-            trj.push(coord);
+            collector.push(uid, &day, coord);
         }
     }
-    if !trj.is_empty() {
-        trjs.push(trj);
-    }
-    trjs
+    collector.into_streams()
 }
 
 /// Determines if content type and parses accordingly.
@@ -213,9 +250,11 @@ pub fn parse_synthetic(content: String) -> Vec<Vec<[f64; 3]>> {
 /// - "mmsi_number,time,longitude,latitude,heading,speed,cog,rot,shipcode" => AIS
 /// - ",latitude,longitude,uid,anom_start" => synthetic data [info](parse_synthetic)
 /// - Otherwise => GPX
-pub fn parse(content: String) -> Vec<Vec<[f64; 3]>> {
+///
+/// Every stream is identified by `source` along with the object and day it describes.
+pub fn parse(content: String, source: &str) -> Vec<(StreamId, Vec<[f64; 3]>)> {
     let line = content.lines().next();
-    if let Some(line) = line {
+    let streams = if let Some(line) = line {
         let line = line.trim().to_ascii_lowercase();
         match line.as_str() {
             "geolife trajectory" => parse_plt(content),
@@ -226,6 +265,13 @@ pub fn parse(content: String) -> Vec<Vec<[f64; 3]>> {
             _ => parse_gpx(content),
         }
     } else {
-        panic!("Nothing to read from stdin!")
-    }
+        panic!("Nothing to read from {}!", source)
+    };
+    streams
+        .into_iter()
+        .map(|(mut id, stream)| {
+            id.source = source.to_string();
+            (id, stream)
+        })
+        .collect()
 }
@@ -1,8 +1,453 @@
+use super::union_find::UnionFind;
+use serde::{Deserialize, Serialize};
 use std::collections::HashSet;
 #[allow(dead_code)]
 type ClusterIdx = usize;
 type MatrixIdx = usize;
 
+/// A merge of two clusters in a [Dendrogram].
+///
+/// Clusters are numbered as in SciPy: the elements are the clusters `0..n`
+/// and the cluster formed by the `i`th merge is `n + i`.
+#[derive(Clone, Copy, Debug, PartialEq)]
+pub struct Merge {
+    pub a: ClusterIdx,
+    pub b: ClusterIdx,
+    /// Distance between `a` and `b` when they were merged.
+    pub distance: f64,
+    /// Number of elements in the merged cluster.
+    pub size: usize,
+}
+
+/// The merges of an agglomerative clustering ordered by distance.
+#[derive(Clone, Debug, Default)]
+pub struct Dendrogram {
+    /// Number of elements.
+    pub n: usize,
+    pub merges: Vec<Merge>,
+}
+
+impl Dendrogram {
+    /// Single-linkage clustering, i.e. the cluster distance is the minimum distance of two elements from separate clusters.
+    ///
+    /// Builds the minimum spanning forest by sweeping over the element pairs sorted by distance (Kruskal),
+    /// keeping track of the clusters using a [UnionFind]. Pairs with non-finite distance are never merged.
+    pub fn single_linkage(distance_matrix: &[Vec<f64>]) -> Self {
+        let n = distance_matrix.len();
+        let mut pairs: Vec<(MatrixIdx, MatrixIdx)> = (0..n)
+            .flat_map(|i| ((i + 1)..n).map(move |j| (i, j)))
+            .filter(|(i, j)| distance_matrix[*i][*j].is_finite())
+            .collect();
+        pairs.sort_by(|(a, b), (c, d)| {
+            distance_matrix[*a][*b]
+                .partial_cmp(&distance_matrix[*c][*d])
+                .unwrap()
+        });
+        let mut components = UnionFind::new(n);
+        // The dendrogram cluster currently represented by each root
+        let mut cluster: Vec<ClusterIdx> = (0..n).collect();
+        let mut size = vec![1; n];
+        let mut merges = vec![];
+        for (i, j) in pairs {
+            let (a, b) = (components.find(i), components.find(j));
+            if components.union(a, b) {
+                let root = components.find(a);
+                let merged = size[a] + size[b];
+                merges.push(Merge {
+                    a: cluster[a],
+                    b: cluster[b],
+                    distance: distance_matrix[i][j],
+                    size: merged,
+                });
+                cluster[root] = n + merges.len() - 1;
+                size[root] = merged;
+            }
+        }
+        Dendrogram { n, merges }
+    }
+
+    /// Agglomerative clustering using the nearest-neighbour chain algorithm and Lance-Williams updates of the cluster distances.
+    ///
+    /// Runs in `O(n²)` time. Clusters at infinite distance from every other cluster are never merged.
+    /// Merges are ordered by distance, which requires the linkage to be monotone, as are all variants of [Linkage].
+    pub fn nn_chain(distance_matrix: &[Vec<f64>], linkage: Linkage) -> Self {
+        let n = distance_matrix.len();
+        let mut d: Vec<Vec<f64>> = distance_matrix.to_vec();
+        let mut active: Vec<bool> = vec![true; n];
+        let mut size: Vec<f64> = vec![1.0; n];
+        // The dendrogram cluster currently stored in each row
+        let mut cluster: Vec<ClusterIdx> = (0..n).collect();
+        let mut merges: Vec<Merge> = vec![];
+        let mut chain: Vec<MatrixIdx> = vec![];
+        loop {
+            if chain.is_empty() {
+                match (0..n).find(|i| active[*i]) {
+                    Some(i) => chain.push(i),
+                    None => break,
+                }
+            }
+            let a = chain[chain.len() - 1];
+            let prev = if chain.len() > 1 {
+                Some(chain[chain.len() - 2])
+            } else {
+                None
+            };
+            // Nearest neighbour of a, preferring the previous element of the chain on ties
+            let mut nearest = prev;
+            for c in (0..n).filter(|c| active[*c] & (*c != a)) {
+                if nearest.is_none_or(|b| d[a][c] < d[a][b]) {
+                    nearest = Some(c);
+                }
+            }
+            let b = match nearest {
+                Some(b) if d[a][b].is_finite() => b,
+                _ => {
+                    // a can't be merged with anything
+                    active[a] = false;
+                    chain.clear();
+                    continue;
+                }
+            };
+            if Some(b) != prev {
+                chain.push(b);
+                continue;
+            }
+            chain.truncate(chain.len() - 2);
+            let d_ab = d[a][b];
+            merges.push(Merge {
+                a: cluster[a],
+                b: cluster[b],
+                distance: d_ab,
+                size: (size[a] + size[b]) as usize,
+            });
+            // Store the merged cluster in row b
+            for k in (0..n).filter(|k| active[*k] & (*k != a) & (*k != b)) {
+                let d_kab = linkage.update(d[k][a], d[k][b], d_ab, size[a], size[b], size[k]);
+                d[k][b] = d_kab;
+                d[b][k] = d_kab;
+            }
+            active[a] = false;
+            size[b] += size[a];
+            cluster[b] = n + merges.len() - 1;
+        }
+
+        // Order by distance and renumber the merged clusters accordingly
+        let mut order: Vec<usize> = (0..merges.len()).collect();
+        order.sort_by(|x, y| {
+            merges[*x]
+                .distance
+                .partial_cmp(&merges[*y].distance)
+                .unwrap()
+        });
+        let mut renumbered: Vec<ClusterIdx> = vec![0; merges.len()];
+        for (new, old) in order.iter().enumerate() {
+            renumbered[*old] = n + new;
+        }
+        let id = |cx: ClusterIdx| if cx < n { cx } else { renumbered[cx - n] };
+        let merges = order
+            .iter()
+            .map(|old| {
+                let merge = merges[*old];
+                Merge {
+                    a: id(merge.a),
+                    b: id(merge.b),
+                    ..merge
+                }
+            })
+            .collect();
+        Dendrogram { n, merges }
+    }
+
+    /// Cuts the dendrogram s.t. only clusters closer than `threshold` are merged.
+    /// Clusters are ordered by their smallest element.
+    pub fn cut(&self, threshold: f64) -> Vec<HashSet<MatrixIdx>> {
+        let mut components = UnionFind::new(self.n);
+        // An element of each dendrogram cluster
+        let mut element: Vec<MatrixIdx> = (0..self.n).collect();
+        for merge in self.merges.iter() {
+            element.push(element[merge.a]);
+            if merge.distance < threshold {
+                components.union(element[merge.a], element[merge.b]);
+            }
+        }
+        components.sets()
+    }
+}
+
+/// Criteria available for clustering elements given their pairwise distances.
+#[derive(Clone, Copy, Debug, PartialEq, Eq, Serialize, Deserialize)]
+#[serde(rename_all = "snake_case")]
+pub enum ClusteringMethod {
+    /// Minimum distance of two elements from separate clusters. Dense elements may chain distinct places together.
+    Single,
+    /// Maximum distance of two elements from separate clusters.
+    Complete,
+    /// Mean distance of the element pairs from separate clusters.
+    Average,
+    /// Increase in variance when merging the clusters, see [Linkage::Ward].
+    Ward,
+    /// Density based clustering with `threshold` as neighbourhood radius, see [dbscan].
+    Dbscan,
+    /// Hierarchical density based clustering, see [hdbscan]. Doesn't use the threshold.
+    Hdbscan,
+}
+
+/// Cluster distances that can be updated using the Lance-Williams formula.
+#[derive(Clone, Copy, Debug, PartialEq, Eq)]
+pub enum Linkage {
+    Complete,
+    Average,
+    /// Ward's minimum variance criterion. Only meaningful for Euclidean distances.
+    Ward,
+}
+
+impl Linkage {
+    /// Returns the distance from cluster `k` to the union of clusters `i` and `j`.
+    fn update(&self, d_ki: f64, d_kj: f64, d_ij: f64, n_i: f64, n_j: f64, n_k: f64) -> f64 {
+        match self {
+            Linkage::Complete => d_ki.max(d_kj),
+            Linkage::Average => (n_i * d_ki + n_j * d_kj) / (n_i + n_j),
+            Linkage::Ward => (((n_i + n_k) * d_ki.powi(2) + (n_j + n_k) * d_kj.powi(2)
+                - n_k * d_ij.powi(2))
+                / (n_i + n_j + n_k))
+                .max(0.0)
+                .sqrt(),
+        }
+    }
+}
+
+/// Clusters the elements of the distance matrix using `method`.
+///
+/// Hierarchical methods merge clusters closer than `threshold`.
+/// Clusters are ordered by their smallest element, and noise found by the density based methods is put in clusters of its own.
+pub fn cluster(
+    distance_matrix: Vec<Vec<f64>>,
+    threshold: f64,
+    method: ClusteringMethod,
+) -> Vec<HashSet<MatrixIdx>> {
+    match method {
+        ClusteringMethod::Single => Clustering::new(distance_matrix, threshold).clusters,
+        ClusteringMethod::Complete => {
+            Dendrogram::nn_chain(&distance_matrix, Linkage::Complete).cut(threshold)
+        }
+        ClusteringMethod::Average => {
+            Dendrogram::nn_chain(&distance_matrix, Linkage::Average).cut(threshold)
+        }
+        ClusteringMethod::Ward => {
+            Dendrogram::nn_chain(&distance_matrix, Linkage::Ward).cut(threshold)
+        }
+        ClusteringMethod::Dbscan => {
+            let n = distance_matrix.len();
+            let neighbours = |i: MatrixIdx, visit: &mut dyn FnMut(MatrixIdx)| {
+                for j in (0..n).filter(|j| (*j != i) && (distance_matrix[i][*j] < threshold)) {
+                    visit(j);
+                }
+            };
+            dbscan(n, neighbours, crate::CONFIG.dbscan_min_points)
+        }
+        ClusteringMethod::Hdbscan => {
+            hdbscan(&distance_matrix, crate::CONFIG.hdbscan_min_cluster_size)
+        }
+    }
+}
+
+/// DBSCAN given the neighbours of each element, i.e. the elements closer than the neighbourhood radius.
+///
+/// `neighbours(i, visit)` calls `visit` with every neighbour of `i` other than `i` itself,
+/// s.t. the neighbourhoods never have to be stored.
+/// Elements with at least `min_points` neighbours, counting themselves, are core elements.
+/// Neighbouring core elements belong to the same cluster, and other elements join the cluster of
+/// a core neighbour. Elements without core neighbours are noise.
+pub fn dbscan<F>(n: usize, neighbours: F, min_points: usize) -> Vec<HashSet<MatrixIdx>>
+where
+    F: Fn(MatrixIdx, &mut dyn FnMut(MatrixIdx)),
+{
+    let is_core: Vec<bool> = (0..n)
+        .map(|i| {
+            let mut count = 1;
+            neighbours(i, &mut |_| count += 1);
+            count >= min_points
+        })
+        .collect();
+    let mut components = UnionFind::new(n);
+    for i in 0..n {
+        if is_core[i] {
+            neighbours(i, &mut |j| {
+                if is_core[j] {
+                    components.union(i, j);
+                }
+            });
+        }
+    }
+    for i in (0..n).filter(|i| !is_core[*i]) {
+        let mut core = None;
+        neighbours(i, &mut |j| {
+            if is_core[j] && core.is_none() {
+                core = Some(j);
+            }
+        });
+        if let Some(core) = core {
+            components.union(i, core);
+        }
+    }
+    components.sets()
+}
+
+/// A cluster of the condensed tree used by [hdbscan].
+struct CondensedCluster {
+    parent: Option<usize>,
+    /// Lambda, i.e. inverse distance, at which the cluster appears.
+    birth: f64,
+    stability: f64,
+    children: Vec<usize>,
+}
+
+/// HDBSCAN, see Campello et al. "Density-Based Clustering Based on Hierarchical Density Estimates" (2013).
+///
+/// Builds the single-linkage dendrogram of the mutual reachability distances, condenses it s.t.
+/// clusters smaller than `min_cluster_size` are considered points falling out of their parent,
+/// and selects the most stable clusters. A tree of the dendrogram may be selected as a whole.
+/// Elements that don't belong to a selected cluster are noise.
+pub fn hdbscan(distance_matrix: &[Vec<f64>], min_cluster_size: usize) -> Vec<HashSet<MatrixIdx>> {
+    let n = distance_matrix.len();
+    let k = min_cluster_size.max(2);
+    // Core distances, i.e. distance to the k'th nearest element counting the element itself
+    let core: Vec<f64> = distance_matrix
+        .iter()
+        .enumerate()
+        .map(|(i, row)| {
+            let mut dists: Vec<f64> = row
+                .iter()
+                .enumerate()
+                .filter(|(j, _)| *j != i)
+                .map(|(_, d)| *d)
+                .collect();
+            dists.sort_by(|a, b| a.partial_cmp(b).unwrap());
+            dists.get(k - 2).copied().unwrap_or(f64::INFINITY)
+        })
+        .collect();
+    let reachability: Vec<Vec<f64>> = (0..n)
+        .map(|i| {
+            (0..n)
+                .map(|j| distance_matrix[i][j].max(core[i]).max(core[j]))
+                .collect()
+        })
+        .collect();
+    let dendrogram = Dendrogram::single_linkage(&reachability);
+    let lambda = |distance: f64| 1.0 / distance.max(f64::EPSILON);
+    let size = |cx: ClusterIdx| {
+        if cx < n {
+            1
+        } else {
+            dendrogram.merges[cx - n].size
+        }
+    };
+    let leaves = |cx: ClusterIdx| {
+        let mut leaves = vec![];
+        let mut stack = vec![cx];
+        while let Some(cx) = stack.pop() {
+            if cx < n {
+                leaves.push(cx);
+            } else {
+                stack.push(dendrogram.merges[cx - n].a);
+                stack.push(dendrogram.merges[cx - n].b);
+            }
+        }
+        leaves
+    };
+
+    // Condense the dendrogram, starting from the root of each tree
+    let mut is_child = vec![false; n + dendrogram.merges.len()];
+    for merge in dendrogram.merges.iter() {
+        is_child[merge.a] = true;
+        is_child[merge.b] = true;
+    }
+    let mut condensed: Vec<CondensedCluster> = vec![];
+    // The condensed cluster each element falls out of
+    let mut fell_out_of: Vec<Option<usize>> = vec![None; n];
+    let mut stack = vec![];
+    for root in (n..is_child.len()).filter(|cx| !is_child[*cx]) {
+        if size(root) < k {
+            continue;
+        }
+        condensed.push(CondensedCluster {
+            parent: None,
+            birth: 0.0,
+            stability: 0.0,
+            children: vec![],
+        });
+        stack.push((root, condensed.len() - 1));
+    }
+    while let Some((cx, cluster)) = stack.pop() {
+        let merge = dendrogram.merges[cx - n];
+        let split = lambda(merge.distance);
+        let birth = condensed[cluster].birth;
+        let (large_a, large_b) = (size(merge.a) >= k, size(merge.b) >= k);
+        if large_a & large_b {
+            for child in [merge.a, merge.b] {
+                condensed[cluster].stability += (split - birth) * size(child) as f64;
+                condensed.push(CondensedCluster {
+                    parent: Some(cluster),
+                    birth: split,
+                    stability: 0.0,
+                    children: vec![],
+                });
+                let child_cluster = condensed.len() - 1;
+                condensed[cluster].children.push(child_cluster);
+                stack.push((child, child_cluster));
+            }
+        } else {
+            for (child, large) in [(merge.a, large_a), (merge.b, large_b)] {
+                if large {
+                    stack.push((child, cluster));
+                } else {
+                    for leaf in leaves(child) {
+                        condensed[cluster].stability += split - birth;
+                        fell_out_of[leaf] = Some(cluster);
+                    }
+                }
+            }
+        }
+    }
+
+    // Select the most stable clusters bottom up. Children are always added after their parents.
+    let mut selected = vec![false; condensed.len()];
+    let mut total = vec![0.0; condensed.len()];
+    for cluster in (0..condensed.len()).rev() {
+        let children: f64 = condensed[cluster].children.iter().map(|c| total[*c]).sum();
+        if condensed[cluster].children.is_empty() | (condensed[cluster].stability >= children) {
+            selected[cluster] = true;
+            total[cluster] = condensed[cluster].stability;
+        } else {
+            total[cluster] = children;
+        }
+    }
+    // Deselect descendants of selected clusters
+    let mut label: Vec<Option<usize>> = vec![None; condensed.len()];
+    for cluster in 0..condensed.len() {
+        let parent_label = condensed[cluster].parent.and_then(|p| label[p]);
+        label[cluster] = parent_label.or(if selected[cluster] {
+            Some(cluster)
+        } else {
+            None
+        });
+    }
+
+    let mut components = UnionFind::new(n);
+    let mut first: Vec<Option<MatrixIdx>> = vec![None; condensed.len()];
+    for (element, cluster) in fell_out_of.iter().enumerate() {
+        if let Some(selected) = cluster.and_then(|c| label[c]) {
+            match first[selected] {
+                Some(other) => {
+                    components.union(element, other);
+                }
+                None => first[selected] = Some(element),
+            }
+        }
+    }
+    components.sets()
+}
+
 pub struct Clustering {
     distance_matrix: Vec<Vec<f64>>,
     pub clusters: Vec<HashSet<MatrixIdx>>,
@@ -15,58 +460,33 @@ impl Clustering {
     /// Initially, each element is considered a cluster containing only the element.
     /// Clusters are merged bottom up until the distance of all clusters exceeds the `threshold`.
     pub fn new(distance_matrix: Vec<Vec<f64>>, threshold: f64) -> Self {
-        let mut clusters: Vec<HashSet<MatrixIdx>> = vec![];
-        for (idx, _) in distance_matrix.iter().enumerate() {
-            let mut set = HashSet::new();
-            set.insert(idx as MatrixIdx);
-            clusters.push(set);
-        }
         let mut c = Clustering {
             distance_matrix,
-            clusters,
+            clusters: vec![],
             threshold,
         };
-        c.prep_distance_matrix();
         c.partition();
         c
     }
 
-    fn cluster_distance(&self, a: ClusterIdx, b: ClusterIdx) -> f64 {
-        let elements_a: Vec<MatrixIdx> = self.clusters[a].iter().copied().collect();
-        let elements_b: Vec<MatrixIdx> = self.clusters[b].iter().copied().collect();
-        let mut dist = f64::INFINITY;
-        for a in elements_a {
-            for b in elements_b.iter() {
-                dist = dist.min(self.distance_matrix[a][*b]);
-            }
-        }
-        dist
+    /// Returns the full merge history, s.t. it can be cut at other thresholds.
+    pub fn dendrogram(&self) -> Dendrogram {
+        Dendrogram::single_linkage(&self.distance_matrix)
     }
 
-    /// We don't want to merge clusters with themselves, so we set internal distance to infinite.
-    fn prep_distance_matrix(&mut self) {
-        let n: MatrixIdx = self.distance_matrix.len();
-        for i in 0..n {
-            self.distance_matrix[i][i] = f64::INFINITY;
-        }
+    fn partition(&mut self) {
+        self.clusters = self.dendrogram().cut(self.threshold);
     }
+}
 
-    fn partition(&mut self) {
-        let mut merging = true;
-        while merging {
-            merging = false;
-            let n: MatrixIdx = self.clusters.len();
-            'outer: for a in 0..n {
-                for b in (a + 1)..n {
-                    let dist = self.cluster_distance(a, b);
-                    if dist < self.threshold {
-                        self.merge_clusters(a, b);
-
-                        merging = true;
-                        break 'outer;
-                    }
-                }
-            }
+/// Manual edits of the clusters, which are only used to test their bookkeeping.
+#[cfg(test)]
+impl Clustering {
+    /// Adds the elements to the cluster with index `cluster`, or to a new cluster if `None`.
+    fn add_to_cluster(&mut self, elements: &[MatrixIdx], cluster: Option<ClusterIdx>) {
+        match cluster {
+            Some(cx) => self.clusters[cx].extend(elements),
+            None => self.clusters.push(elements.iter().copied().collect()),
         }
     }
 
@@ -144,4 +564,128 @@ mod test {
         let c = Clustering::new(distances, 1.1);
         assert_eq!(c.clusters.len(), 1);
     }
+
+    #[test]
+    fn single_linkage_skips_infinite_distances() {
+        let inf = f64::INFINITY;
+        let distances = vec![vec![0., 1., inf], vec![1., 0., inf], vec![inf, inf, 0.]];
+        let dendrogram = Dendrogram::single_linkage(&distances);
+        assert_eq!(dendrogram.merges.len(), 1);
+        assert_eq!(
+            dendrogram.cut(inf),
+            vec![HashSet::from([0, 1]), HashSet::from([2])]
+        );
+    }
+
+    #[test]
+    fn dendrogram_can_be_cut_at_any_threshold() {
+        // Elements on a line at 0, 1, 3 and 10
+        let xs = [0.0f64, 1.0, 3.0, 10.0];
+        let distances = xs
+            .iter()
+            .map(|a| xs.iter().map(|b| (a - b).abs()).collect())
+            .collect();
+        let c = Clustering::new(distances, 1.5);
+        let dendrogram = c.dendrogram();
+        assert_eq!(
+            dendrogram.merges,
+            vec![
+                Merge {
+                    a: 0,
+                    b: 1,
+                    distance: 1.0,
+                    size: 2
+                },
+                Merge {
+                    a: 4,
+                    b: 2,
+                    distance: 2.0,
+                    size: 3
+                },
+                Merge {
+                    a: 5,
+                    b: 3,
+                    distance: 7.0,
+                    size: 4
+                },
+            ]
+        );
+        assert_eq!(c.clusters, dendrogram.cut(1.5));
+        assert_eq!(dendrogram.cut(0.5).len(), 4);
+        assert_eq!(dendrogram.cut(1.5).len(), 3);
+        assert_eq!(dendrogram.cut(2.5)[0], HashSet::from([0, 1, 2]));
+        assert_eq!(dendrogram.cut(7.5).len(), 1);
+    }
+
+    /// Two groups of three elements on a line, where a bridge of elements chains them under single linkage.
+    fn bridged_groups() -> Vec<Vec<f64>> {
+        let xs = [0.0f64, 1.0, 2.0, 4.0, 6.0, 8.0, 9.0, 10.0];
+        xs.iter()
+            .map(|a| xs.iter().map(|b| (a - b).abs()).collect())
+            .collect()
+    }
+
+    #[test]
+    fn linkages() {
+        assert_eq!(
+            cluster(bridged_groups(), 2.5, ClusteringMethod::Single).len(),
+            1
+        );
+        for method in [
+            ClusteringMethod::Complete,
+            ClusteringMethod::Average,
+            ClusteringMethod::Ward,
+        ] {
+            let clusters = cluster(bridged_groups(), 2.5, method);
+            assert!(clusters.len() > 1, "{:?}", method);
+            assert!(clusters[0].contains(&0) & clusters[0].contains(&1));
+            assert!(!clusters.iter().any(|c| c.contains(&0) & c.contains(&7)));
+        }
+    }
+
+    #[test]
+    fn nn_chain_complete_linkage() {
+        let dendrogram = Dendrogram::nn_chain(&bridged_groups(), Linkage::Complete);
+        assert_eq!(dendrogram.merges.len(), 7);
+        assert!(dendrogram
+            .merges
+            .windows(2)
+            .all(|m| m[0].distance <= m[1].distance));
+        assert_eq!(dendrogram.merges[6].distance, 10.0);
+        assert_eq!(dendrogram.merges[6].size, 8);
+    }
+
+    #[test]
+    fn dbscan_noise_and_border() {
+        // 0-1-2 are dense, 3 borders 2, and 4 is noise
+        let pairs = [(0, 1), (0, 2), (1, 2), (2, 3)];
+        let neighbours = |i: MatrixIdx, visit: &mut dyn FnMut(MatrixIdx)| {
+            for (a, b) in pairs.iter() {
+                if *a == i {
+                    visit(*b);
+                } else if *b == i {
+                    visit(*a);
+                }
+            }
+        };
+        let clusters = dbscan(5, neighbours, 3);
+        assert_eq!(clusters.len(), 2);
+        assert_eq!(clusters[0], HashSet::from([0, 1, 2, 3]));
+        assert_eq!(clusters[1], HashSet::from([4]));
+    }
+
+    #[test]
+    fn hdbscan_separates_groups() {
+        // Two tight groups far apart and an outlier
+        let xs = [0.0f64, 1.0, 2.0, 3.0, 100.0, 101.0, 102.0, 103.0, 500.0];
+        let distances: Vec<Vec<f64>> = xs
+            .iter()
+            .map(|a| xs.iter().map(|b| (a - b).abs()).collect())
+            .collect();
+        let clusters = hdbscan(&distances, 3);
+        assert_eq!(clusters.len(), 3);
+        assert_eq!(clusters[0], HashSet::from([0, 1, 2, 3]));
+        assert_eq!(clusters[1], HashSet::from([4, 5, 6, 7]));
+        assert_eq!(clusters[2], HashSet::from([8]));
+    }
 }
@@ -0,0 +1,114 @@
+//! Cyclic time
+//!
+//! Timestamps are the number of ms since midnight, or since midnight on Monday when `--weekly` is given.
+//! Time thus wraps around at the end of each period, s.t. 23:58 and 00:02 are four minutes apart.
+//! Intervals are represented by `t1 <= t2`, where `t2` may exceed the period if the interval wraps around.
+use chrono::{Datelike, NaiveDate};
+
+use crate::CLUSTERINGARGS;
+
+pub const MS_IN_24H: f64 = 24.0 * 60.0 * 60.0 * 1000.0;
+pub const MS_IN_WEEK: f64 = 7.0 * MS_IN_24H;
+
+/// Returns the length of the cycle timestamps wrap around.
+pub fn period() -> f64 {
+    if CLUSTERINGARGS.lock().unwrap().weekly {
+        MS_IN_WEEK
+    } else {
+        MS_IN_24H
+    }
+}
+
+/// Returns the shortest distance between `a` and `b` around the cycle.
+pub fn distance(a: f64, b: f64, period: f64) -> f64 {
+    let d = (a - b).rem_euclid(period);
+    d.min(period - d)
+}
+
+/// Returns `t` shifted by a whole number of periods s.t. it is as close as possible to the interval `[t1, t2]`.
+pub fn unwrap_into(t: f64, t1: f64, t2: f64, period: f64) -> f64 {
+    let after = t - ((t - t1) / period).floor() * period;
+    let before = after - period;
+    if (after - t2).max(0.0) <= (t1 - before).max(0.0) {
+        after
+    } else {
+        before
+    }
+}
+
+/// Returns true if `t` lies in the interval `[t1, t2]` when wrapping around the cycle.
+pub fn contains(t1: f64, t2: f64, t: f64, period: f64) -> bool {
+    (t1..=t2).contains(&unwrap_into(t, t1, t2, period))
+}
+
+/// Returns the shortest interval containing all of `ts` on the cycle.
+///
+/// The interval starts right after the largest gap between consecutive timestamps.
+/// If that gap wraps around, the interval is simply the minimum and maximum timestamp.
+pub fn covering_interval(ts: &[f64], period: f64) -> (f64, f64) {
+    assert!(!ts.is_empty(), "Need timestamps to find covering interval!");
+    let mut sorted: Vec<f64> = ts.iter().map(|t| t.rem_euclid(period)).collect();
+    sorted.sort_by(|a, b| a.partial_cmp(b).unwrap());
+    let (first, last) = (sorted[0], sorted[sorted.len() - 1]);
+    // Start with the gap wrapping around the end of the cycle
+    let mut gap = (first + period - last, 0);
+    for (i, pair) in sorted.windows(2).enumerate() {
+        if pair[1] - pair[0] > gap.0 {
+            gap = (pair[1] - pair[0], i + 1);
+        }
+    }
+    match gap.1 {
+        0 => {
+            let t1 = ts.iter().copied().fold(f64::INFINITY, f64::min);
+            let t2 = ts.iter().copied().fold(f64::NEG_INFINITY, f64::max);
+            (t1, t2)
+        }
+        i => (sorted[i], sorted[i - 1] + period),
+    }
+}
+
+/// Returns the time from midnight on Monday to midnight on `day`, which is formatted as `YYYY-MM-DD`.
+pub fn weekday_offset(day: &str) -> Option<f64> {
+    let date = NaiveDate::parse_from_str(day, "%Y-%m-%d").ok()?;
+    Some(date.weekday().num_days_from_monday() as f64 * MS_IN_24H)
+}
+
+#[cfg(test)]
+mod test {
+    use super::*;
+
+    const MINUTE: f64 = 60.0 * 1000.0;
+
+    #[test]
+    fn distance_wraps_around_midnight() {
+        let before = MS_IN_24H - 2.0 * MINUTE;
+        assert_eq!(distance(before, 2.0 * MINUTE, MS_IN_24H), 4.0 * MINUTE);
+        assert_eq!(distance(MINUTE, 3.0 * MINUTE, MS_IN_24H), 2.0 * MINUTE);
+    }
+
+    #[test]
+    fn interval_around_midnight() {
+        let ts = [MS_IN_24H - 2.0 * MINUTE, MINUTE, 2.0 * MINUTE];
+        let (t1, t2) = covering_interval(&ts, MS_IN_24H);
+        assert_eq!(
+            (t1, t2),
+            (MS_IN_24H - 2.0 * MINUTE, MS_IN_24H + 2.0 * MINUTE)
+        );
+        assert!(contains(t1, t2, MINUTE, MS_IN_24H));
+        assert!(!contains(t1, t2, 3.0 * MINUTE, MS_IN_24H));
+        // Intervals that don't wrap are left untouched
+        let ts = [MINUTE, 60.0 * MINUTE];
+        assert_eq!(covering_interval(&ts, MS_IN_24H), (MINUTE, 60.0 * MINUTE));
+        assert_eq!(
+            unwrap_into(MINUTE, MINUTE, 60.0 * MINUTE, MS_IN_24H),
+            MINUTE
+        );
+    }
+
+    #[test]
+    fn weekday_offsets() {
+        assert_eq!(weekday_offset("2023-01-02"), Some(0.0));
+        assert_eq!(weekday_offset("2023-01-08"), Some(6.0 * MS_IN_24H));
+        assert_eq!(weekday_offset(""), None);
+    }
+}
@@ -0,0 +1,152 @@
+//! DTW barycenter averaging
+//!
+//! Averages trajectories after aligning them using dynamic time warping, see
+//! Petitjean et al., A global averaging method for dynamic time warping, with applications to clustering (2011).
+//! Unlike the [centroid](super::trajectory::weighted_centroid), which stretches trajectories linearly in time,
+//! trajectories pausing at different points are aligned before they are averaged.
+use super::get_distance;
+use super::trajectory::Monotone;
+
+/// Returns the weighted DBA average of the trajectories, each given with its weight.
+///
+/// Starting from `initial`, every point of the average is repeatedly moved to the weighted mean
+/// of the points aligned to it, until the average no longer changes or `iterations` is reached.
+/// Its first and last point are the weighted mean of the first and last points of the trajectories.
+pub fn dba(trjs: &[(f64, &[[f64; 3]])], initial: &[[f64; 3]], iterations: usize) -> Vec<[f64; 3]> {
+    assert!(!initial.is_empty(), "Need initial average to run DBA!");
+    let mut average = initial.to_vec();
+    for _ in 0..iterations {
+        let mut sums = vec![([0.0; 3], 0.0); average.len()];
+        for (weight, trj) in trjs {
+            for (i, j) in dtw_path(&average, trj) {
+                let (sum, total) = &mut sums[i];
+                for (s, v) in sum.iter_mut().zip(trj[j].iter()) {
+                    *s += weight * v;
+                }
+                *total += weight;
+            }
+        }
+        let updated: Vec<[f64; 3]> = sums
+            .iter()
+            .map(|(sum, total)| [sum[0] / total, sum[1] / total, sum[2] / total])
+            .collect();
+        if updated == average {
+            break;
+        }
+        average = updated;
+    }
+    // The average starts and ends where the trajectories do on average,
+    // s.t. it stays within the nodes they connect
+    let total: f64 = trjs.iter().map(|(weight, _)| weight).sum();
+    let mean = |idx: fn(&[[f64; 3]]) -> usize| {
+        let mut mean = [0.0; 3];
+        for (weight, trj) in trjs {
+            for (m, v) in mean.iter_mut().zip(trj[idx(trj)].iter()) {
+                *m += weight * v / total;
+            }
+        }
+        mean
+    };
+    let (start, end) = (mean(|_| 0), mean(|trj| trj.len() - 1));
+    let mut trj = vec![start];
+    trj.extend(
+        average
+            .into_iter()
+            .filter(|[_, _, t]| (start[2] < *t) & (*t < end[2])),
+    );
+    trj.push(end);
+    // Points aligned to the same points share their timestamp
+    trj.make_monotone()
+}
+
+/// Returns the pairs of indices of the optimal DTW alignment of `trj_a` and `trj_b`, ordered from start to end.
+fn dtw_path(trj_a: &[[f64; 3]], trj_b: &[[f64; 3]]) -> Vec<(usize, usize)> {
+    let (n, m) = (trj_a.len(), trj_b.len());
+    let mut cost = vec![vec![f64::INFINITY; m]; n];
+    for i in 0..n {
+        for j in 0..m {
+            let d = get_distance(&trj_a[i], &trj_b[j]);
+            cost[i][j] = d + match (i, j) {
+                (0, 0) => 0.0,
+                (0, _) => cost[0][j - 1],
+                (_, 0) => cost[i - 1][0],
+                _ => cost[i - 1][j - 1].min(cost[i - 1][j]).min(cost[i][j - 1]),
+            };
+        }
+    }
+    // Backtrack from the end, preferring the diagonal on ties
+    let (mut i, mut j) = (n - 1, m - 1);
+    let mut path = vec![(i, j)];
+    while (i, j) != (0, 0) {
+        let next = match (i, j) {
+            (0, _) => (0, j - 1),
+            (_, 0) => (i - 1, 0),
+            _ => {
+                let diagonal = cost[i - 1][j - 1];
+                if diagonal <= cost[i - 1][j] && diagonal <= cost[i][j - 1] {
+                    (i - 1, j - 1)
+                } else if cost[i - 1][j] <= cost[i][j - 1] {
+                    (i - 1, j)
+                } else {
+                    (i, j - 1)
+                }
+            }
+        };
+        i = next.0;
+        j = next.1;
+        path.push((i, j));
+    }
+    path.reverse();
+    path
+}
+
+#[cfg(test)]
+mod test {
+    use super::*;
+
+    #[test]
+    fn align_pauses_before_averaging() {
+        // Both travel from x=0 to x=40, but pause at different places
+        let trj_a = [
+            [0., 0., 0.],
+            [10., 0., 1.],
+            [10., 0., 2.],
+            [10., 0., 3.],
+            [20., 0., 4.],
+            [30., 0., 5.],
+            [40., 0., 6.],
+        ];
+        let trj_b = [
+            [0., 2., 0.],
+            [10., 2., 1.],
+            [20., 2., 2.],
+            [30., 2., 3.],
+            [30., 2., 4.],
+            [30., 2., 5.],
+            [40., 2., 6.],
+        ];
+        let initial = [
+            [0., 1., 0.],
+            [10., 1., 1.],
+            [20., 1., 3.],
+            [30., 1., 4.],
+            [40., 1., 6.],
+        ];
+        let average = dba(&[(1.0, &trj_a), (1.0, &trj_b)], &initial, 10);
+        assert!(average.is_monotone());
+        // Pauses are aligned to the same place instead of being smeared between places
+        for [x, _, _] in average.iter() {
+            assert_eq!(x % 10.0, 0.0);
+        }
+    }
+
+    #[test]
+    fn path_covers_both_trajectories() {
+        let trj_a = [[0., 0., 0.], [10., 0., 1.], [20., 0., 2.]];
+        let trj_b = [[0., 0., 0.], [10., 0., 1.], [10., 0., 2.], [20., 0., 3.]];
+        assert_eq!(
+            dtw_path(&trj_a, &trj_b),
+            vec![(0, 0), (1, 1), (1, 2), (2, 3)]
+        );
+    }
+}
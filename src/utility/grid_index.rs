@@ -0,0 +1,289 @@
+use super::union_find::UnionFind;
+use super::{cyclic_time, Bbox};
+use std::collections::HashMap;
+
+type Cell = [i64; 3];
+
+/// Uniform grid over 3D points for fixed radius neighbour queries.
+///
+/// The cells have a diagonal no longer than the query radius, s.t. points sharing a cell are always neighbours,
+/// while every neighbour of a point lies within a few cells of the cell of the point.
+/// The third dimension may be periodic, in which case the cells wrap around at the end of the period.
+pub struct GridIndex {
+    radius: f64,
+    /// Side lengths of the cells, where the third may be shortened to divide the period evenly.
+    cell_size: [f64; 3],
+    /// Number of cells along each dimension that may contain neighbours of a point.
+    reach: [i64; 3],
+    /// Period of the third dimension along with the number of cells it is divided into.
+    period: Option<(f64, i64)>,
+    points: Vec<[f64; 3]>,
+    cells: HashMap<Cell, Vec<usize>>,
+}
+
+impl GridIndex {
+    /// Indexes `points` for queries of neighbours closer than `radius`.
+    pub fn new(points: Vec<[f64; 3]>, radius: f64) -> Self {
+        let side = radius / 3f64.sqrt();
+        Self::build(points, radius, [side; 3], None)
+    }
+
+    /// Indexes `points` whose third coordinate wraps around after `period`.
+    pub fn periodic(points: Vec<[f64; 3]>, radius: f64, period: f64) -> Self {
+        assert!(period > 0.0, "Period must be positive");
+        let side = radius / 3f64.sqrt();
+        let cycle_cells = ((period / side).ceil() as i64).max(1);
+        let cell_size = [side, side, period / cycle_cells as f64];
+        Self::build(points, radius, cell_size, Some((period, cycle_cells)))
+    }
+
+    fn build(
+        points: Vec<[f64; 3]>,
+        radius: f64,
+        cell_size: [f64; 3],
+        period: Option<(f64, i64)>,
+    ) -> Self {
+        assert!(radius > 0.0, "Radius must be positive");
+        let reach = |side: f64| (radius / side).ceil() as i64;
+        let mut index = GridIndex {
+            radius,
+            cell_size,
+            reach: [
+                reach(cell_size[0]),
+                reach(cell_size[1]),
+                reach(cell_size[2]),
+            ],
+            period,
+            points: vec![],
+            cells: HashMap::new(),
+        };
+        for (idx, point) in points.iter().enumerate() {
+            let cell = index.cell(point);
+            index.cells.entry(cell).or_default().push(idx);
+        }
+        index.points = points;
+        index
+    }
+
+    fn cell(&self, point: &[f64; 3]) -> Cell {
+        let c = |v: f64, side: f64| (v / side).floor() as i64;
+        let z = match self.period {
+            Some((period, cycle_cells)) => {
+                c(point[2].rem_euclid(period), self.cell_size[2]).min(cycle_cells - 1)
+            }
+            None => c(point[2], self.cell_size[2]),
+        };
+        [
+            c(point[0], self.cell_size[0]),
+            c(point[1], self.cell_size[1]),
+            z,
+        ]
+    }
+
+    /// Returns the occupied cells that may contain neighbours of points in `cell`, including `cell` itself.
+    fn neighbouring_cells(&self, cell: &Cell) -> Vec<Cell> {
+        let [rx, ry, rz] = self.reach;
+        let mut layers: Vec<i64> = (-rz..=rz)
+            .map(|dz| match self.period {
+                Some((_, cycle_cells)) => (cell[2] + dz).rem_euclid(cycle_cells),
+                None => cell[2] + dz,
+            })
+            .collect();
+        layers.sort_unstable();
+        layers.dedup();
+        let mut cells = vec![];
+        for x in (cell[0] - rx)..=(cell[0] + rx) {
+            for y in (cell[1] - ry)..=(cell[1] + ry) {
+                for z in layers.iter() {
+                    let neighbour = [x, y, *z];
+                    if self.cells.contains_key(&neighbour) {
+                        cells.push(neighbour);
+                    }
+                }
+            }
+        }
+        cells
+    }
+
+    /// Returns the distance between `p` and `q`, wrapping around the third dimension if periodic.
+    pub fn distance(&self, p: &[f64; 3], q: &[f64; 3]) -> f64 {
+        let dt = match self.period {
+            Some((period, _)) => cyclic_time::distance(p[2], q[2], period),
+            None => p[2] - q[2],
+        };
+        ((p[0] - q[0]).powi(2) + (p[1] - q[1]).powi(2) + dt.powi(2)).sqrt()
+    }
+
+    /// Calls `visit` with every other point closer than the radius to point `i`.
+    pub fn for_each_neighbour(&self, i: usize, mut visit: impl FnMut(usize)) {
+        let p = &self.points[i];
+        for cell in self.neighbouring_cells(&self.cell(p)) {
+            for j in self.cells[&cell].iter().copied() {
+                if (j != i) && (self.distance(p, &self.points[j]) < self.radius) {
+                    visit(j);
+                }
+            }
+        }
+    }
+
+    /// Returns the connected components of the graph where points closer than the radius are adjacent.
+    ///
+    /// Points sharing a cell are joined without comparing them, and two cells are only compared
+    /// until a pair of neighbours is found or they already belong to the same component.
+    pub fn components(&self) -> UnionFind {
+        let mut components = UnionFind::new(self.points.len());
+        for idxs in self.cells.values() {
+            for j in idxs.iter().skip(1) {
+                components.union(idxs[0], *j);
+            }
+        }
+        for (cell, idxs) in self.cells.iter() {
+            for other in self.neighbouring_cells(cell) {
+                let others = &self.cells[&other];
+                // Each pair of cells is compared once
+                if (other <= *cell) || (components.find(idxs[0]) == components.find(others[0])) {
+                    continue;
+                }
+                let adjacent = idxs.iter().any(|i| {
+                    others
+                        .iter()
+                        .any(|j| self.distance(&self.points[*i], &self.points[*j]) < self.radius)
+                });
+                if adjacent {
+                    components.union(idxs[0], others[0]);
+                }
+            }
+        }
+        components
+    }
+}
+
+/// Uniform spatial grid over bounding boxes for point location queries.
+///
+/// Each box is registered in every cell it overlaps, s.t. only the boxes of a single cell are tested per query.
+pub struct BboxIndex {
+    cell_size: f64,
+    bboxs: Vec<Bbox>,
+    cells: HashMap<[i64; 2], Vec<usize>>,
+}
+
+impl BboxIndex {
+    /// Indexes `bboxs` using square cells with sides of length `cell_size`.
+    pub fn new(bboxs: Vec<Bbox>, cell_size: f64) -> Self {
+        assert!(cell_size > 0.0, "Cell size must be positive");
+        let mut cells: HashMap<[i64; 2], Vec<usize>> = HashMap::new();
+        let c = |v: f64| (v / cell_size).floor() as i64;
+        for (idx, bbox) in bboxs.iter().enumerate() {
+            for x in c(bbox.x1)..=c(bbox.x2) {
+                for y in c(bbox.y1)..=c(bbox.y2) {
+                    cells.entry([x, y]).or_default().push(idx);
+                }
+            }
+        }
+        BboxIndex {
+            cell_size,
+            bboxs,
+            cells,
+        }
+    }
+
+    /// Returns the indices of all boxes containing `point` spatially and temporally.
+    pub fn containing(&self, point: &[f64; 3]) -> Vec<usize> {
+        let c = |v: f64| (v / self.cell_size).floor() as i64;
+        match self.cells.get(&[c(point[0]), c(point[1])]) {
+            Some(idxs) => idxs
+                .iter()
+                .copied()
+                .filter(|idx| self.bboxs[*idx].contains_point(point))
+                .collect(),
+            None => vec![],
+        }
+    }
+}
+
+#[cfg(test)]
+mod test {
+    use super::*;
+
+    /// Deterministic pseudo random points in a cube of side 100.
+    fn random_points(n: usize) -> Vec<[f64; 3]> {
+        let mut seed = 7u64;
+        let mut next = || {
+            seed = seed
+                .wrapping_mul(6364136223846793005)
+                .wrapping_add(1442695040888963407);
+            (seed >> 33) as f64 / (1u64 << 31) as f64 * 100.0 - 50.0
+        };
+        (0..n).map(|_| [next(), next(), next()]).collect()
+    }
+
+    #[test]
+    fn neighbours_match_brute_force() {
+        let points = random_points(300);
+        let index = GridIndex::new(points.clone(), 7.5);
+        let mut found = 0;
+        for i in 0..points.len() {
+            let mut neighbours = vec![];
+            index.for_each_neighbour(i, |j| neighbours.push(j));
+            neighbours.sort_unstable();
+            let expected: Vec<usize> = (0..points.len())
+                .filter(|j| (*j != i) && (index.distance(&points[i], &points[*j]) < 7.5))
+                .collect();
+            assert_eq!(neighbours, expected);
+            found += neighbours.len();
+        }
+        assert!(found > 0);
+    }
+
+    #[test]
+    fn components_match_brute_force() {
+        let points = random_points(300);
+        let index = GridIndex::new(points.clone(), 7.5);
+        let mut expected = UnionFind::new(points.len());
+        for i in 0..points.len() {
+            for j in (i + 1)..points.len() {
+                if index.distance(&points[i], &points[j]) < 7.5 {
+                    expected.union(i, j);
+                }
+            }
+        }
+        let expected = expected.sets();
+        assert!(expected.len() > 1 && expected.len() < points.len());
+        assert_eq!(index.components().sets(), expected);
+    }
+
+    #[test]
+    fn neighbours_wrap_around_period() {
+        let points = vec![[0.0, 0.0, 1.0], [0.0, 0.0, 99.0], [0.0, 0.0, 50.0]];
+        let index = GridIndex::periodic(points, 5.0, 100.0);
+        let mut neighbours = vec![];
+        index.for_each_neighbour(0, |j| neighbours.push(j));
+        assert_eq!(neighbours, vec![1]);
+        assert_eq!(index.components().sets().len(), 2);
+    }
+
+    #[test]
+    fn find_containing_bboxs() {
+        let bbox = |x1: f64, x2: f64, t1: f64, t2: f64| Bbox {
+            x1,
+            x2,
+            y1: 0.0,
+            y2: 10.0,
+            t1,
+            t2,
+        };
+        let index = BboxIndex::new(
+            vec![
+                bbox(0.0, 10.0, 0.0, 5.0),
+                bbox(0.0, 10.0, 6.0, 9.0),
+                bbox(400.0, 900.0, 0.0, 9.0),
+            ],
+            100.0,
+        );
+        assert_eq!(index.containing(&[5.0, 5.0, 2.0]), vec![0]);
+        assert_eq!(index.containing(&[5.0, 5.0, 7.0]), vec![1]);
+        assert_eq!(index.containing(&[650.0, 5.0, 7.0]), vec![2]);
+        assert!(index.containing(&[5.0, 5.0, 5.5]).is_empty());
+        assert!(index.containing(&[200.0, 5.0, 2.0]).is_empty());
+    }
+}
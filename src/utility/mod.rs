@@ -5,19 +5,25 @@
 pub mod bounding_box;
 pub mod ch_filter;
 pub mod clustering;
+pub mod cyclic_time;
+pub mod dba;
+pub mod grid_index;
 pub mod line;
 pub mod motion_detector;
+pub mod similarity;
 mod stop_detector;
 pub mod time_guard;
 pub mod timeout_handler;
+mod tradis;
 pub mod trajectory;
+pub mod transport_mode;
+pub mod union_find;
 pub mod visvalingam;
 #[doc(inline)]
 pub use self::stop_detector::StopDetector;
 pub use bounding_box::Bbox;
 #[doc(inline)]
 pub use ch_filter::CHFilter;
-pub use clustering::Clustering;
 pub use motion_detector::MotionDetector;
 pub use time_guard::clean_stream;
 pub use timeout_handler::TimeoutHandler;
@@ -26,10 +32,88 @@ pub use line::Line;
 pub use stop_detector::IsStopped;
 pub use visvalingam::visvalingam;
 
+use std::borrow::Cow;
+use std::collections::HashSet;
+
 /// Returns Euclidean distance in meters
 ///
+/// Only the first two coordinates are used, s.t. both `[x, y]` and `[x, y, t]` points can be given.
+///
 /// # Examples
 /// todo!
-pub fn get_distance(from: &[f64; 3], to: &[f64; 3]) -> f64 {
+pub fn get_distance(from: &[f64], to: &[f64]) -> f64 {
     ((from[0] - to[0]).powi(2) + (from[1] - to[1]).powi(2)).sqrt()
 }
+
+/// Returns the field as it should be written to a csv file.
+///
+/// Fields containing a comma, a quote or a line break are quoted, doubling any quotes within them.
+pub fn csv_field(field: &str) -> Cow<'_, str> {
+    if field.contains([',', '"', '\n', '\r']) {
+        Cow::Owned(format!("\"{}\"", field.replace('"', "\"\"")))
+    } else {
+        Cow::Borrowed(field)
+    }
+}
+
+/// Returns a distinct folder name for each of the names, in the same order.
+///
+/// Characters other than alphanumerics, `-` and `_` are replaced by `_`, so no name can leave the folder it is placed in.
+/// Names that become equal are told apart by a numbered suffix.
+pub fn folder_names<'a>(names: impl IntoIterator<Item = &'a str>) -> Vec<String> {
+    let mut used = HashSet::new();
+    names
+        .into_iter()
+        .map(|name| {
+            let mut folder =
+                name.replace(|c: char| !c.is_alphanumeric() && c != '-' && c != '_', "_");
+            if folder.is_empty() {
+                folder.push('_');
+            }
+            let mut unique = folder.clone();
+            let mut suffix = 1;
+            while !used.insert(unique.clone()) {
+                suffix += 1;
+                unique = format!("{}_{}", folder, suffix);
+            }
+            unique
+        })
+        .collect()
+}
+
+#[cfg(test)]
+mod test {
+    use super::*;
+
+    #[test]
+    fn quote_csv_fields() {
+        assert_eq!(csv_field("data/day.gpx"), "data/day.gpx");
+        assert_eq!(csv_field("a,b.csv"), "\"a,b.csv\"");
+        assert_eq!(csv_field("say \"hi\""), "\"say \"\"hi\"\"\"");
+    }
+
+    #[test]
+    fn distinct_folders_within_output() {
+        let names = [
+            "bob smith",
+            "bob_smith",
+            "bob_smith_2",
+            "..",
+            ".",
+            "",
+            "257000001",
+        ];
+        assert_eq!(
+            folder_names(names),
+            vec![
+                "bob_smith",
+                "bob_smith_2",
+                "bob_smith_2_2",
+                "__",
+                "_",
+                "__2",
+                "257000001"
+            ]
+        );
+    }
+}
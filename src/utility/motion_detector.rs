@@ -8,7 +8,7 @@ pub struct MotionDetector {
     min_velocity: f64,
     /// Was the last point a 'stop'
     was_stopped: IsStopped,
-    /// If (min_velocity + eps > average velocity) then it is moving
+    /// If the average velocity exceeds (min_velocity + eps) then it is moving
     eps: f64,
     /// Temporal intervals in timespan
     tmp_ivls: Vec<f64>,
@@ -31,8 +31,8 @@ impl MotionDetector {
         }
     }
 
-    /// calculate average velocity of points in tmp_ivls and spt_ivls
-    fn get_avg_velocity(&self) -> f64 {
+    /// calculate average velocity (km/h) of points in tmp_ivls and spt_ivls
+    pub fn get_avg_velocity(&self) -> f64 {
         let dist: f64 = self.spt_ivls.clone().into_iter().sum();
         let span: f64 = self.tmp_ivls.clone().into_iter().sum();
         let h: f64 = span / 3600000.0;
@@ -60,14 +60,17 @@ impl MotionDetector {
                 }
             }
             let km_h = self.get_avg_velocity();
-            let is_stopped = (self.min_velocity + self.eps) < km_h;
-            match (is_stopped, self.was_stopped) {
-                (true, IsStopped::Maybe) => self.was_stopped = IsStopped::Yes,
-                (true, IsStopped::Yes) | (false, IsStopped::No) => {}
-                (true, IsStopped::No) | (false, IsStopped::Yes) => {
+            // Slower than `min_velocity` means stopped, faster than `min_velocity + eps` means moving.
+            // In between, the object keeps its current state.
+            let is_stopped = km_h < self.min_velocity;
+            let is_moving = (self.min_velocity + self.eps) < km_h;
+            match (is_stopped, is_moving, self.was_stopped) {
+                (true, _, IsStopped::Maybe) => self.was_stopped = IsStopped::Yes,
+                (true, _, IsStopped::No) | (_, true, IsStopped::Yes) => {
                     self.was_stopped = IsStopped::Maybe
                 }
-                (false, IsStopped::Maybe) => self.was_stopped = IsStopped::No,
+                (_, true, IsStopped::Maybe) => self.was_stopped = IsStopped::No,
+                _ => {}
             }
         } else {
             self.ref_pt = Some(*point);
@@ -76,3 +79,50 @@ impl MotionDetector {
         self.was_stopped
     }
 }
+
+#[cfg(test)]
+mod test {
+    use super::*;
+
+    /// Returns the states of the detector for points every 10 seconds, `meters` apart.
+    fn states(meters: f64, n: usize) -> Vec<IsStopped> {
+        let mut md = MotionDetector::new();
+        (0..n)
+            .map(|i| md.is_stopped(&[i as f64 * meters, 0.0, i as f64 * 10000.0]))
+            .collect()
+    }
+
+    #[test]
+    fn stopped_below_minimum_velocity() {
+        // 1m per 10s is 0.36 km/h
+        assert_eq!(
+            states(1.0, 4),
+            vec![
+                IsStopped::Maybe,
+                IsStopped::Yes,
+                IsStopped::Yes,
+                IsStopped::Yes
+            ]
+        );
+    }
+
+    #[test]
+    fn moving_above_minimum_velocity_and_eps() {
+        // 100m per 10s is 36 km/h
+        assert_eq!(
+            states(100.0, 4),
+            vec![
+                IsStopped::Maybe,
+                IsStopped::No,
+                IsStopped::No,
+                IsStopped::No
+            ]
+        );
+    }
+
+    #[test]
+    fn keep_state_between_minimum_velocity_and_eps() {
+        // 9m per 10s is 3.24 km/h
+        assert_eq!(states(9.0, 3), vec![IsStopped::Maybe; 3]);
+    }
+}
@@ -0,0 +1,245 @@
+//! Trajectory similarity measures
+//!
+//! Distances between trajectories used to cluster the trajectories of an edge.
+//! Hausdorff only compares shapes, whereas the remaining measures respect the direction of travel
+//! and, in case of EDR, LCSS and TRADIS, the timing as well.
+use serde::{Deserialize, Serialize};
+use trajectory_similarity::hausdorff;
+
+use super::tradis;
+use super::trajectory::{Timespan, Trajectory};
+use crate::CONFIG;
+
+#[derive(Clone, Copy, Debug, PartialEq, Eq, Serialize, Deserialize)]
+#[serde(rename_all = "snake_case")]
+pub enum SimilarityMeasure {
+    /// Hausdorff distance in meters, ignoring direction and time.
+    Hausdorff,
+    /// Discrete Fréchet distance in meters.
+    Frechet,
+    /// Dynamic time warping cost per point of the longer trajectory, in meters.
+    Dtw,
+    /// Edit distance on real sequences, relative to the length of the longer trajectory.
+    Edr,
+    /// One minus the longest common subsequence relative to the length of the shorter trajectory.
+    Lcss,
+    /// Area between the trajectories during their common timespan divided by its duration, in meters.
+    Tradis,
+}
+
+impl SimilarityMeasure {
+    /// Returns the distance between `trj_a` and `trj_b`. Lower is more similar.
+    pub fn distance(&self, trj_a: &[[f64; 3]], trj_b: &[[f64; 3]]) -> f64 {
+        match self {
+            SimilarityMeasure::Hausdorff => hausdorff::similarity(trj_a, trj_b),
+            SimilarityMeasure::Frechet => frechet(trj_a, trj_b),
+            SimilarityMeasure::Dtw => dtw(trj_a, trj_b),
+            SimilarityMeasure::Edr => edr(trj_a, trj_b),
+            SimilarityMeasure::Lcss => lcss(trj_a, trj_b),
+            SimilarityMeasure::Tradis => tradis_distance(trj_a, trj_b),
+        }
+    }
+
+    /// Returns the configured threshold below which trajectories are considered similar.
+    pub fn threshold(&self) -> f64 {
+        match self {
+            SimilarityMeasure::Hausdorff => CONFIG.max_hausdorff_meters,
+            SimilarityMeasure::Frechet => CONFIG.max_frechet_meters,
+            SimilarityMeasure::Dtw => CONFIG.max_dtw_meters,
+            SimilarityMeasure::Edr => CONFIG.max_edr,
+            SimilarityMeasure::Lcss => CONFIG.max_lcss,
+            SimilarityMeasure::Tradis => CONFIG.max_tradis_meters,
+        }
+    }
+}
+
+fn spatial_distance(p: &[f64; 3], q: &[f64; 3]) -> f64 {
+    ((p[0] - q[0]).powi(2) + (p[1] - q[1]).powi(2)).sqrt()
+}
+
+/// Two points match if they are within `similarity_epsilon_meters` and `similarity_epsilon_ms` of each other.
+fn matches(p: &[f64; 3], q: &[f64; 3]) -> bool {
+    (spatial_distance(p, q) <= CONFIG.similarity_epsilon_meters)
+        & ((p[2] - q[2]).abs() <= CONFIG.similarity_epsilon_ms)
+}
+
+/// Returns the distance if either trajectory is empty: none between two empty trajectories, otherwise `unmatched`.
+fn distance_to_empty(trj_a: &[[f64; 3]], trj_b: &[[f64; 3]], unmatched: f64) -> Option<f64> {
+    match (trj_a.is_empty(), trj_b.is_empty()) {
+        (true, true) => Some(0.0),
+        (false, false) => None,
+        _ => Some(unmatched),
+    }
+}
+
+/// Discrete Fréchet distance, i.e. the shortest leash allowing both to traverse their trajectory without backtracking.
+pub fn frechet(trj_a: &[[f64; 3]], trj_b: &[[f64; 3]]) -> f64 {
+    if let Some(distance) = distance_to_empty(trj_a, trj_b, f64::INFINITY) {
+        return distance;
+    }
+    let m = trj_b.len();
+    let mut prev = vec![f64::INFINITY; m];
+    for (i, p) in trj_a.iter().enumerate() {
+        let mut row = vec![f64::INFINITY; m];
+        for (j, q) in trj_b.iter().enumerate() {
+            let d = spatial_distance(p, q);
+            row[j] = match (i, j) {
+                (0, 0) => d,
+                (0, _) => row[j - 1].max(d),
+                (_, 0) => prev[0].max(d),
+                _ => prev[j].min(prev[j - 1]).min(row[j - 1]).max(d),
+            };
+        }
+        prev = row;
+    }
+    prev[m - 1]
+}
+
+/// Dynamic time warping cost divided by the length of the longer trajectory.
+pub fn dtw(trj_a: &[[f64; 3]], trj_b: &[[f64; 3]]) -> f64 {
+    if let Some(distance) = distance_to_empty(trj_a, trj_b, f64::INFINITY) {
+        return distance;
+    }
+    let m = trj_b.len();
+    let mut prev = vec![f64::INFINITY; m];
+    for (i, p) in trj_a.iter().enumerate() {
+        let mut row = vec![f64::INFINITY; m];
+        for (j, q) in trj_b.iter().enumerate() {
+            let d = spatial_distance(p, q);
+            row[j] = d + match (i, j) {
+                (0, 0) => 0.0,
+                (0, _) => row[j - 1],
+                (_, 0) => prev[0],
+                _ => prev[j].min(prev[j - 1]).min(row[j - 1]),
+            };
+        }
+        prev = row;
+    }
+    prev[m - 1] / trj_a.len().max(m) as f64
+}
+
+/// Number of edits needed to turn one trajectory into the other, relative to the length of the longer trajectory.
+pub fn edr(trj_a: &[[f64; 3]], trj_b: &[[f64; 3]]) -> f64 {
+    if let Some(distance) = distance_to_empty(trj_a, trj_b, 1.0) {
+        return distance;
+    }
+    let m = trj_b.len();
+    let mut prev: Vec<usize> = (0..=m).collect();
+    for (i, p) in trj_a.iter().enumerate() {
+        let mut row = vec![i + 1; m + 1];
+        for (j, q) in trj_b.iter().enumerate() {
+            let substitution = if matches(p, q) { 0 } else { 1 };
+            row[j + 1] = (prev[j] + substitution)
+                .min(prev[j + 1] + 1)
+                .min(row[j] + 1);
+        }
+        prev = row;
+    }
+    prev[m] as f64 / trj_a.len().max(m) as f64
+}
+
+/// One minus the length of the longest common subsequence relative to the length of the shorter trajectory.
+pub fn lcss(trj_a: &[[f64; 3]], trj_b: &[[f64; 3]]) -> f64 {
+    if let Some(distance) = distance_to_empty(trj_a, trj_b, 1.0) {
+        return distance;
+    }
+    let m = trj_b.len();
+    let mut prev = vec![0usize; m + 1];
+    for p in trj_a.iter() {
+        let mut row = vec![0usize; m + 1];
+        for (j, q) in trj_b.iter().enumerate() {
+            row[j + 1] = if matches(p, q) {
+                prev[j] + 1
+            } else {
+                prev[j + 1].max(row[j])
+            };
+        }
+        prev = row;
+    }
+    1.0 - prev[m] as f64 / trj_a.len().min(m) as f64
+}
+
+/// Area between the trajectories during their common timespan using [tradis](tradis::similarity),
+/// divided by the duration of the timespan.
+/// Trajectories that don't overlap in time are infinitely far apart.
+pub fn tradis_distance(trj_a: &[[f64; 3]], trj_b: &[[f64; 3]]) -> f64 {
+    let (trj_a, trj_b): (Trajectory, Trajectory) = (trj_a.to_vec(), trj_b.to_vec());
+    let (t1, t2) = trj_a.common_timespan(&trj_b);
+    if t1 >= t2 {
+        return f64::INFINITY;
+    }
+    let trj_a = trj_a.trim_to_timespan((t1, t2));
+    let trj_b = trj_b.trim_to_timespan((t1, t2));
+    if (trj_a.len() < 2) | (trj_b.len() < 2) {
+        return f64::INFINITY;
+    }
+    tradis::similarity(&trj_a, &trj_b) / (t2 - t1)
+}
+
+#[cfg(test)]
+mod test {
+    use super::*;
+
+    const MINUTE: f64 = 60.0 * 1000.0;
+
+    /// Travels east from x=0 to x=1000 at y, starting at `start`.
+    fn eastbound(y: f64, start: f64) -> Vec<[f64; 3]> {
+        (0..=10)
+            .map(|i| [i as f64 * 100.0, y, start + i as f64 * MINUTE])
+            .collect()
+    }
+
+    #[test]
+    fn measures_respect_direction() {
+        let a = eastbound(0.0, 0.0);
+        let mut b = eastbound(20.0, 0.0);
+        assert!((frechet(&a, &b) - 20.0).abs() < 1e-9);
+        assert!((dtw(&a, &b) - 20.0).abs() < 1e-9);
+        assert_eq!(edr(&a, &b), 0.0);
+        assert_eq!(lcss(&a, &b), 0.0);
+        // The same route travelled westbound has the same shape
+        b.reverse();
+        for (p, t) in b.iter_mut().zip(a.iter().map(|p| p[2])) {
+            p[2] = t;
+        }
+        assert!(hausdorff::similarity(&a, &b) < 30.0);
+        assert!(frechet(&a, &b) > 900.0);
+        assert!(dtw(&a, &b) > 100.0);
+        assert!(edr(&a, &b) > 0.5);
+        assert!(lcss(&a, &b) > 0.5);
+    }
+
+    #[test]
+    fn measures_respect_timing() {
+        let a = eastbound(0.0, 0.0);
+        let b = eastbound(0.0, 60.0 * MINUTE);
+        assert_eq!(frechet(&a, &b), 0.0);
+        assert_eq!(edr(&a, &b), 1.0);
+        assert_eq!(lcss(&a, &b), 1.0);
+        assert_eq!(tradis_distance(&a, &b), f64::INFINITY);
+    }
+
+    #[test]
+    fn measures_of_empty_trajectories() {
+        let a = eastbound(0.0, 0.0);
+        // Distances in meters are infinite, relative distances are one
+        for (measure, unmatched) in [
+            (frechet as fn(&_, &_) -> f64, f64::INFINITY),
+            (dtw, f64::INFINITY),
+            (edr, 1.0),
+            (lcss, 1.0),
+        ] {
+            assert_eq!(measure(&[], &[]), 0.0);
+            assert_eq!(measure(&a, &[]), unmatched);
+            assert_eq!(measure(&[], &a), unmatched);
+        }
+    }
+
+    #[test]
+    fn tradis_is_average_gap() {
+        let a = eastbound(0.0, 0.0);
+        let b = eastbound(20.0, 0.0);
+        // The triangulation of the area between the trajectories is approximate
+        assert!((tradis_distance(&a, &b) - 20.0).abs() < 2.0);
+    }
+}
@@ -6,8 +6,8 @@ use crate::CONFIG;
 ///
 /// # Example
 /// ``` rust
-/// fn object_is_stopped(stream:Vec<[f64;3]>, config:Config) -> Vec<([f64;3],bool)> {
-///     let mut sd = StopDetector::new(&config);
+/// fn object_is_stopped(stream:Vec<[f64;3]>) -> Vec<([f64;3],IsStopped)> {
+///     let mut sd = StopDetector::new();
 ///     stream
 ///         .into_iter()
 ///         .map(|point| (point,sd.is_stopped(point)))
@@ -70,39 +70,37 @@ mod test {
     use super::*;
     #[test]
     fn too_litle_time() {
-        let config = Config::default();
         // Default time limit is 15 minutes
-        let mut sd = StopDetector::new(&config);
-        let points = vec![[0., 0., 0.], [0., 0., 5. * 60000.], [0., 0., 10. * 60000.]];
-        for point in points {
-            assert_eq!(sd.is_stopped(&point), IsStopped::Maybe, "t:{}", point[2]);
+        let mut sd = StopDetector::new();
+        let points = [[0., 0., 0.], [0., 0., 5. * 60000.], [0., 0., 10. * 60000.]];
+        assert_eq!(sd.is_stopped(&points[0]), IsStopped::No);
+        for point in &points[1..] {
+            assert_eq!(sd.is_stopped(point), IsStopped::Maybe, "t:{}", point[2]);
         }
     }
 
     #[test]
     fn too_large_area() {
-        let config = Config::default();
         // Default size limit is 50 meters
-        let mut sd = StopDetector::new(&config);
-        let points = vec![[0., 0., 0.], [0., 51., 5. * 60000.]];
-        assert_eq!(sd.is_stopped(&points[0]), IsStopped::Maybe);
+        let mut sd = StopDetector::new();
+        let points = [[0., 0., 0.], [0., 51., 5. * 60000.]];
+        assert_eq!(sd.is_stopped(&points[0]), IsStopped::No);
         assert_eq!(sd.is_stopped(&points[1]), IsStopped::No);
     }
 
     #[test]
     fn perfect_fit() {
-        let config = Config::default();
         // Default time limit is 15 minutes
         // Default size limit is 50 meters
-        let mut sd = StopDetector::new(&config);
-        let points = vec![
+        let mut sd = StopDetector::new();
+        let points = [
             [0., 0., 0.],
             [0., 10., 5. * 60000.],
             [10., 10., 10. * 60000.],
             [15., 15., 16. * 60000.],
             [20., 35., 20. * 60000.],
         ];
-        assert_eq!(sd.is_stopped(&points[0]), IsStopped::Maybe);
+        assert_eq!(sd.is_stopped(&points[0]), IsStopped::No);
         assert_eq!(sd.is_stopped(&points[1]), IsStopped::Maybe);
         assert_eq!(sd.is_stopped(&points[2]), IsStopped::Maybe);
         assert_eq!(sd.is_stopped(&points[3]), IsStopped::Yes);
@@ -112,21 +110,21 @@ mod test {
     #[test]
     fn reset_after_start() {
         // Once an object started moving, reset the Bbox
-        let config = Config::default();
         // Default time limit is 15 minutes
         // Default size limit is 50 meters
-        let mut sd = StopDetector::new(&config);
-        let points = vec![
-            [0., 0., 0.],             // Maybe
+        let mut sd = StopDetector::new();
+        let points = [
+            [0., 0., 0.],             // No
             [0., 10., 20. * 60000.],  // Yes
             [0., 51., 25. * 60000.],  // No
-            [15., 51., 30. * 60000.], // Maybe
-            [25., 25., 41. * 60000.], // Yes
+            [15., 51., 30. * 60000.], // No
+            [25., 25., 41. * 60000.], // No
         ];
-        assert_eq!(sd.is_stopped(&points[0]), IsStopped::Maybe);
+        assert_eq!(sd.is_stopped(&points[0]), IsStopped::No);
         assert_eq!(sd.is_stopped(&points[1]), IsStopped::Yes);
         assert_eq!(sd.is_stopped(&points[2]), IsStopped::No);
-        assert_eq!(sd.is_stopped(&points[3]), IsStopped::Maybe);
-        assert_eq!(sd.is_stopped(&points[4]), IsStopped::Yes, "{:?}", sd);
+        // The detector keeps returning `No` until it is reset, which `build_path` does once the object moves
+        assert_eq!(sd.is_stopped(&points[3]), IsStopped::No);
+        assert_eq!(sd.is_stopped(&points[4]), IsStopped::No, "{:?}", sd);
     }
 }
@@ -1,8 +1,5 @@
 use itertools::Itertools;
 
-use crate::visvalingam;
-use crate::CONFIG;
-
 pub type Trajectory = Vec<[f64; 3]>;
 
 pub trait Monotone {
@@ -89,28 +86,62 @@ impl Timespan for Trajectory {
     }
 }
 
-pub fn merge(trj_a: &[[f64; 3]], trj_b: &[[f64; 3]]) -> Vec<[f64; 3]> {
-    let (trj_a, trj_b) = align_start_time(trj_a, trj_b);
-    let (trj_a, trj_b) = morph_to_fit(&trj_a, &trj_b);
-    let trj = average(&trj_a, &trj_b);
+/// Returns the weighted centroid of the trajectories, each given with its weight.
+///
+/// The trajectories are first fitted to the weighted mean start time and duration using [fit_to_timespan].
+/// The centroid is sampled at every timestamp of the fitted trajectories as their weighted mean position at that time,
+/// s.t. it does not depend on the order of the trajectories.
+pub fn weighted_centroid(trjs: &[(f64, &[[f64; 3]])]) -> Vec<[f64; 3]> {
+    assert!(!trjs.is_empty(), "Need trajectories to find centroid!");
+    let total: f64 = trjs.iter().map(|(weight, _)| weight).sum();
+    let start = trjs
+        .iter()
+        .map(|(weight, trj)| weight * trj[0][2])
+        .sum::<f64>()
+        / total;
+    let duration = trjs
+        .iter()
+        .map(|(weight, trj)| weight * (trj[trj.len() - 1][2] - trj[0][2]))
+        .sum::<f64>()
+        / total;
+    let fitted: Vec<Vec<[f64; 3]>> = trjs
+        .iter()
+        .map(|(_, trj)| fit_to_timespan(trj, start, duration))
+        .collect();
+    let mut timestamps: Vec<f64> = fitted.iter().flatten().map(|[_, _, t]| *t).collect();
+    timestamps.sort_by(|a, b| a.partial_cmp(b).unwrap());
+    let timestamps = timestamps.make_monotone();
+    let mut interpolators: Vec<Interpolator> = fitted.into_iter().map(Interpolator::from).collect();
+    let trj: Vec<[f64; 3]> = timestamps
+        .into_iter()
+        .map(|t| {
+            let (x, y) = interpolators.iter_mut().zip(trjs.iter()).fold(
+                (0.0, 0.0),
+                |(x, y), (interpolator, (weight, _))| {
+                    let [px, py, _] = interpolator.get_point(t);
+                    (x + weight * px, y + weight * py)
+                },
+            );
+            [x / total, y / total, t]
+        })
+        .collect();
     assert!(trj.is_monotone());
-    visvalingam(&trj, CONFIG.visvalingam_threshold)
+    trj
 }
 
-fn average(trj_a: &[[f64; 3]], trj_b: &[[f64; 3]]) -> Vec<[f64; 3]> {
-    let mut timestamps: Vec<f64> = trj_a.iter().map(|[_, _, t]| *t).collect();
-    timestamps.extend(trj_b.iter().map(|[_, _, t]| *t));
-    timestamps.sort_by(|a, b| a.partial_cmp(b).unwrap());
-    timestamps = timestamps.make_monotone();
-    let mut trj: Vec<[f64; 3]> = vec![];
-    let mut trj_a = Interpolator::from(trj_a.to_vec());
-    let mut trj_b = Interpolator::from(trj_b.to_vec());
-    for t in timestamps {
-        let point_a = trj_a.get_point(t);
-        let point_b = trj_b.get_point(t);
-        trj.push(mean_point(&point_a, &point_b));
-    }
-    trj
+/// Shifts and linearly stretches the trajectory in time s.t. it starts at `start` and lasts `duration`.
+fn fit_to_timespan(trj: &[[f64; 3]], start: f64, duration: f64) -> Vec<[f64; 3]> {
+    let (t1, t2) = (trj[0][2], trj[trj.len() - 1][2]);
+    let factor = if t2 > t1 { duration / (t2 - t1) } else { 0.0 };
+    let mut fitted: Vec<[f64; 3]> = trj
+        .iter()
+        .map(|[x, y, t]| [*x, *y, start + (t - t1) * factor])
+        .collect();
+    // Ensure all fitted trajectories end at exactly the same timestamp
+    // (avoid floating point errors)
+    let last = fitted.len() - 1;
+    fitted[last][2] = start + duration;
+    fitted
 }
 
 struct Interpolator {
@@ -161,88 +192,28 @@ fn interpolate(t: &f64, p: &[f64; 3], q: &[f64; 3]) -> [f64; 3] {
     }
 }
 
-fn mean_point(a: &[f64; 3], b: &[f64; 3]) -> [f64; 3] {
-    let x: f64 = (a[0] + b[0]) / 2.;
-    let y: f64 = (a[1] + b[1]) / 2.;
-    let t: f64 = a[2];
-    [x, y, t]
-}
-
-fn align_start_time(trj_a: &[[f64; 3]], trj_b: &[[f64; 3]]) -> (Vec<[f64; 3]>, Vec<[f64; 3]>) {
-    let mut switch: bool = false;
-    let (trj_a, trj_b) = if trj_a[0][2] < trj_b[0][2] {
-        (trj_a, trj_b)
-    } else {
-        switch = true;
-        (trj_b, trj_a)
-    };
-    let dt: f64 = (trj_b[0][2] - trj_a[0][2]) / 2.;
-    let trj_a = trj_a
-        .iter()
-        .map(|c| [c[0], c[1], c[2] + dt])
-        .collect::<Vec<[f64; 3]>>();
-    let trj_b = trj_b
-        .iter()
-        .map(|c| [c[0], c[1], c[2] - dt])
-        .collect::<Vec<[f64; 3]>>();
-    if switch {
-        (trj_b, trj_a)
-    } else {
-        (trj_a, trj_b)
-    }
-}
-
-fn morph_to_fit(trj_a: &[[f64; 3]], trj_b: &[[f64; 3]]) -> (Vec<[f64; 3]>, Vec<[f64; 3]>) {
-    let mut switch: bool = false;
-    let (trj_a, trj_b) = if trj_a[trj_a.len() - 1][2] < trj_b[trj_b.len() - 1][2] {
-        (trj_a, trj_b)
-    } else {
-        switch = true;
-        (trj_b, trj_a)
-    };
-    let delta_a = trj_a[trj_a.len() - 1][2] - trj_a[0][2];
-    let delta_b = trj_b[trj_b.len() - 1][2] - trj_b[0][2];
-    let target: f64 = delta_a + ((delta_b - delta_a) / 2.0);
-    let factor_a = delta_a / target;
-    let factor_b = delta_b / target;
-    let trj_a = trj_a
-        .iter()
-        .map(|[x, y, t]| [*x, *y, ((*t - trj_a[0][2]) / factor_a) + trj_a[0][2]])
-        .collect::<Vec<[f64; 3]>>();
-    let trj_b = trj_b
-        .iter()
-        .map(|[x, y, t]| [*x, *y, ((*t - trj_b[0][2]) / factor_b) + trj_b[0][2]])
-        .collect::<Vec<[f64; 3]>>();
-    // Ensure timespans starts and ends at exactly the same timestamp
-    // (avoid floating point errors)
-    let (idx_a, idx_b) = (trj_a.len() - 1, trj_b.len() - 1);
-    let mut trj_a = trj_a;
-    let mut trj_b = trj_b;
-    trj_a[0][2] = trj_b[0][2].min(trj_a[0][2]);
-    trj_b[0][2] = trj_a[0][2];
-    trj_a[idx_a][2] = trj_b[idx_b][2].max(trj_a[idx_a][2]);
-    trj_b[idx_b][2] = trj_a[idx_a][2];
-    let trj_a = trj_a;
-    if !switch {
-        (trj_a, trj_b)
-    } else {
-        (trj_b, trj_a)
-    }
-}
-
 #[cfg(test)]
 mod test {
     use super::*;
     #[test]
-    fn average_test() {}
+    fn centroid_weighs_trajectories_by_count() {
+        let trj_a = [[0., 0., 0.], [10., 0., 10.]];
+        let trj_b = [[0., 4., 0.], [10., 4., 10.]];
+        let centroid = weighted_centroid(&[(3.0, &trj_a), (1.0, &trj_b)]);
+        assert_eq!(centroid, vec![[0., 1., 0.], [10., 1., 10.]]);
+        // The order of the trajectories doesn't matter
+        let centroid_ba = weighted_centroid(&[(1.0, &trj_b), (3.0, &trj_a)]);
+        assert_eq!(centroid, centroid_ba);
+    }
 
     #[test]
-    fn morph_25_16_test() {
+    fn stretch_to_common_duration() {
         // trj_a should be stretched by 25% and trj_b should be
         // shrunk by (approx.) 16.66% s.t. they end at the same time.
         let trj_a = [[0., 0., 0.], [1., 0., 1.], [2., 0., 2.], [4., 0., 4.]];
         let trj_b = [[0., 0., 0.], [3., 0., 3.], [6., 0., 6.]];
-        let (trj_a, trj_b) = morph_to_fit(&trj_a, &trj_b);
+        let trj_a = fit_to_timespan(&trj_a, 0.0, 5.0);
+        let trj_b = fit_to_timespan(&trj_b, 0.0, 5.0);
         assert_eq!([0., 0., 0.0], trj_a[0]);
         assert_eq!([1., 0., 1.25], trj_a[1]);
         assert_eq!([2., 0., 2.5], trj_a[2]);
@@ -254,10 +225,10 @@ mod test {
     }
 
     #[test]
-    fn align_start_time_test() {
+    fn shift_to_common_start_time() {
+        // Starting at 0.5 and 0.0, both trajectories are shifted to start at 0.25
         let trj_a = [[0., 0., 0.5], [1., 0., 1.], [2., 0., 2.], [3., 0., 3.]];
-        let trj_b = [[0., 0., 0.], [0., 1., 1.], [0., 2., 2.], [0., 3., 3.]];
-        let (trj_a, _trj_b) = align_start_time(&trj_a, &trj_b);
+        let trj_a = fit_to_timespan(&trj_a, 0.25, 2.5);
         assert_eq!([0., 0., 0.25], trj_a[0]);
         assert_eq!([1., 0., 0.75], trj_a[1]);
         assert_eq!([2., 0., 1.75], trj_a[2]);
@@ -0,0 +1,214 @@
+use super::get_distance;
+use crate::CONFIG;
+use serde::{Deserialize, Serialize};
+use std::fmt::Display;
+
+/// Means of transportation used while traversing a trajectory.
+#[derive(Clone, Copy, Debug, PartialEq, Eq, Hash, PartialOrd, Ord, Serialize, Deserialize)]
+pub enum TransportMode {
+    Stationary,
+    Walk,
+    Bike,
+    Car,
+    PublicTransport,
+    Vessel,
+}
+
+impl TransportMode {
+    pub const ALL: [TransportMode; 6] = [
+        TransportMode::Stationary,
+        TransportMode::Walk,
+        TransportMode::Bike,
+        TransportMode::Car,
+        TransportMode::PublicTransport,
+        TransportMode::Vessel,
+    ];
+}
+
+impl Display for TransportMode {
+    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+        let name = match self {
+            TransportMode::Stationary => "stationary",
+            TransportMode::Walk => "walk",
+            TransportMode::Bike => "bike",
+            TransportMode::Car => "car",
+            TransportMode::PublicTransport => "public_transport",
+            TransportMode::Vessel => "vessel",
+        };
+        write!(f, "{}", name)
+    }
+}
+
+/// Kinematic features of a trajectory used to infer its [TransportMode].
+#[derive(Clone, Copy, Debug, Default)]
+pub struct ModeFeatures {
+    /// Length divided by duration in km/h.
+    pub mean_speed: f64,
+    /// 95th percentile of the speed between consecutive points in km/h.
+    pub p95_speed: f64,
+    /// Mean absolute acceleration in m/s².
+    pub mean_acceleration: f64,
+    /// Number of times the object comes to a halt per km.
+    pub stops_per_km: f64,
+}
+
+impl ModeFeatures {
+    pub fn new(trj: &[[f64; 3]]) -> Self {
+        // Speeds (m/s) and durations (s) of the intervals between consecutive points
+        let intervals: Vec<(f64, f64)> = trj
+            .windows(2)
+            .map(|pts| {
+                (
+                    get_distance(&pts[0], &pts[1]),
+                    (pts[1][2] - pts[0][2]) / 1000.0,
+                )
+            })
+            .filter(|(_, dt)| *dt > 0.0)
+            .map(|(dist, dt)| (dist / dt, dt))
+            .collect();
+        if intervals.is_empty() {
+            return ModeFeatures::default();
+        }
+        let length: f64 = trj
+            .windows(2)
+            .map(|pts| get_distance(&pts[0], &pts[1]))
+            .sum();
+        let duration: f64 = intervals.iter().map(|(_, dt)| dt).sum();
+        let mut speeds: Vec<f64> = intervals.iter().map(|(v, _)| v * 3.6).collect();
+        let halts = speeds
+            .windows(2)
+            .filter(|v| (v[0] >= CONFIG.stationary_km_h) & (v[1] < CONFIG.stationary_km_h))
+            .count();
+        let mean_acceleration = if intervals.len() > 1 {
+            intervals
+                .windows(2)
+                .map(|ivls| (ivls[1].0 - ivls[0].0).abs() / ivls[1].1)
+                .sum::<f64>()
+                / (intervals.len() - 1) as f64
+        } else {
+            0.0
+        };
+        speeds.sort_by(|a, b| a.partial_cmp(b).unwrap());
+        let p95_idx = ((speeds.len() - 1) as f64 * 0.95).round() as usize;
+        ModeFeatures {
+            mean_speed: length / duration * 3.6,
+            p95_speed: speeds[p95_idx],
+            mean_acceleration,
+            stops_per_km: if length > 0.0 {
+                halts as f64 / (length / 1000.0)
+            } else {
+                0.0
+            },
+        }
+    }
+}
+
+/// Infers the transport mode of a trajectory from its [features](ModeFeatures).
+///
+/// Rules of thumb:
+/// - Barely moving, see `stationary_km_h` of the config: stationary.
+/// - Moving while the input describes vessels (`--vessels`): vessel.
+/// - Top speeds of a pedestrian: walk. Top speeds of a cyclist with moderate acceleration: bike.
+/// - Faster with frequent halts that pull the mean speed well below the top speed,
+///   like buses stopping every few hundred meters: public transport.
+/// - Otherwise: car.
+pub fn classify(trj: &[[f64; 3]], vessels: bool) -> TransportMode {
+    let features = ModeFeatures::new(trj);
+    if features.p95_speed < CONFIG.stationary_km_h {
+        TransportMode::Stationary
+    } else if vessels {
+        TransportMode::Vessel
+    } else if features.p95_speed < 9.0 {
+        TransportMode::Walk
+    } else if (features.p95_speed < 28.0) & (features.mean_acceleration < 1.0) {
+        TransportMode::Bike
+    } else if (features.stops_per_km >= 1.0) & (features.mean_speed < 0.6 * features.p95_speed) {
+        TransportMode::PublicTransport
+    } else {
+        TransportMode::Car
+    }
+}
+
+#[cfg(test)]
+mod test {
+    use super::*;
+
+    /// Moves east at `km_h` with a point every 10 seconds.
+    /// The object halts for a minute after every `halt_every` meters.
+    fn trj(km_h: f64, minutes: usize, halt_every: Option<f64>) -> Vec<[f64; 3]> {
+        let step = km_h / 3.6 * 10.0;
+        let mut trj = vec![[0.0, 0.0, 0.0]];
+        let mut since_halt = 0.0;
+        while trj.len() < minutes * 6 {
+            let [x, y, t] = trj[trj.len() - 1];
+            if let Some(halt_every) = halt_every {
+                if since_halt >= halt_every {
+                    since_halt = 0.0;
+                    for i in 1..=6 {
+                        trj.push([x, y, t + i as f64 * 10000.0]);
+                    }
+                    continue;
+                }
+            }
+            since_halt += step;
+            trj.push([x + step, y, t + 10000.0]);
+        }
+        trj
+    }
+
+    #[test]
+    fn stationary() {
+        assert_eq!(
+            classify(&trj(0.5, 10, None), false),
+            TransportMode::Stationary
+        );
+    }
+
+    #[test]
+    fn walk() {
+        assert_eq!(classify(&trj(5.0, 10, None), false), TransportMode::Walk);
+    }
+
+    #[test]
+    fn bike() {
+        assert_eq!(classify(&trj(18.0, 10, None), false), TransportMode::Bike);
+    }
+
+    #[test]
+    fn car() {
+        assert_eq!(classify(&trj(60.0, 10, None), false), TransportMode::Car);
+    }
+
+    #[test]
+    fn vessel() {
+        assert_eq!(classify(&trj(20.0, 10, None), true), TransportMode::Vessel);
+        assert_eq!(
+            classify(&trj(0.5, 10, None), true),
+            TransportMode::Stationary
+        );
+    }
+
+    #[test]
+    fn halt_below_stationary_speed() {
+        // Every other interval of 10 seconds is just too slow to leave a trajectory stationary
+        let slow = CONFIG.stationary_km_h * 0.9 / 3.6 * 10.0;
+        let fast = 20.0 / 3.6 * 10.0;
+        let mut trj = vec![[0.0, 0.0, 0.0]];
+        for i in 1..=10 {
+            let [x, y, t] = trj[trj.len() - 1];
+            let step = if i % 2 == 0 { slow } else { fast };
+            trj.push([x + step, y, t + 10000.0]);
+        }
+        let features = ModeFeatures::new(&trj);
+        let km = trj[10][0] / 1000.0;
+        assert!((features.stops_per_km - 5.0 / km).abs() < 1e-9);
+    }
+
+    #[test]
+    fn public_transport() {
+        assert_eq!(
+            classify(&trj(40.0, 30, Some(400.0)), false),
+            TransportMode::PublicTransport
+        );
+    }
+}
@@ -0,0 +1,85 @@
+use std::collections::{HashMap, HashSet};
+
+/// Disjoint sets over the elements `0..n` with path compression and union by size.
+#[derive(Clone, Debug)]
+pub struct UnionFind {
+    parent: Vec<usize>,
+    size: Vec<usize>,
+}
+
+impl UnionFind {
+    /// Puts each of the `n` elements in a set of its own.
+    pub fn new(n: usize) -> Self {
+        UnionFind {
+            parent: (0..n).collect(),
+            size: vec![1; n],
+        }
+    }
+
+    /// Returns the representative of the set containing `a`.
+    pub fn find(&mut self, a: usize) -> usize {
+        let mut root = a;
+        while self.parent[root] != root {
+            root = self.parent[root];
+        }
+        let mut a = a;
+        while self.parent[a] != root {
+            let next = self.parent[a];
+            self.parent[a] = root;
+            a = next;
+        }
+        root
+    }
+
+    /// Merges the sets containing `a` and `b`.
+    /// Returns false if they were already in the same set.
+    pub fn union(&mut self, a: usize, b: usize) -> bool {
+        let (a, b) = (self.find(a), self.find(b));
+        if a == b {
+            return false;
+        }
+        let (large, small) = if self.size[a] >= self.size[b] {
+            (a, b)
+        } else {
+            (b, a)
+        };
+        self.parent[small] = large;
+        self.size[large] += self.size[small];
+        true
+    }
+
+    /// Returns the sets ordered by their smallest element.
+    pub fn sets(&mut self) -> Vec<HashSet<usize>> {
+        let mut sets: Vec<HashSet<usize>> = vec![];
+        let mut index: HashMap<usize, usize> = HashMap::new();
+        for a in 0..self.parent.len() {
+            let root = self.find(a);
+            let idx = *index.entry(root).or_insert_with(|| {
+                sets.push(HashSet::new());
+                sets.len() - 1
+            });
+            sets[idx].insert(a);
+        }
+        sets
+    }
+}
+
+#[cfg(test)]
+mod test {
+    use super::*;
+
+    #[test]
+    fn union_and_sets() {
+        let mut uf = UnionFind::new(5);
+        assert!(uf.union(0, 3));
+        assert!(uf.union(3, 4));
+        assert!(!uf.union(4, 0));
+        assert_eq!(uf.find(0), uf.find(4));
+        assert_ne!(uf.find(0), uf.find(1));
+        let sets = uf.sets();
+        assert_eq!(sets.len(), 3);
+        assert_eq!(sets[0], HashSet::from([0, 3, 4]));
+        assert_eq!(sets[1], HashSet::from([1]));
+        assert_eq!(sets[2], HashSet::from([2]));
+    }
+}